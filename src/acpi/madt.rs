@@ -0,0 +1,130 @@
+//! Multiple APIC Description Table, listing the local APICs and I/O APICs of the machine and
+//! how the ISA IRQs are wired to them.
+//! https://wiki.osdev.org/MADT
+
+use crate::acpi::{find_table, Sdt};
+use core::ptr::read_unaligned;
+
+const ENTRY_LOCAL_APIC: u8 = 0;
+const ENTRY_IO_APIC: u8 = 1;
+const ENTRY_INTERRUPT_OVERRIDE: u8 = 2;
+
+/// MPS INTI flags of an interrupt source override.
+const POLARITY_MASK: u16 = 0b11;
+const POLARITY_ACTIVE_LOW: u16 = 0b11;
+const TRIGGER_MASK: u16 = 0b11 << 2;
+const TRIGGER_LEVEL: u16 = 0b11 << 2;
+
+#[allow(dead_code)]
+#[derive(Debug, Clone, Copy)]
+pub enum MadtEntry {
+    LocalApic {
+        processor_id: u8,
+        apic_id: u8,
+        flags: u32,
+    },
+    IoApic {
+        id: u8,
+        address: u32,
+        /// First Global System Interrupt handled by this I/O APIC.
+        gsi_base: u32,
+    },
+    /// An ISA IRQ wired to another GSI than its number, or with another polarity or trigger.
+    InterruptOverride {
+        bus: u8,
+        source: u8,
+        gsi: u32,
+        flags: u16,
+    },
+    Other(u8),
+}
+
+/// Polarity of an interrupt source override, ISA interrupts default to active high.
+pub fn is_active_low(flags: u16) -> bool {
+    flags & POLARITY_MASK == POLARITY_ACTIVE_LOW
+}
+
+/// Trigger mode of an interrupt source override, ISA interrupts default to edge triggered.
+pub fn is_level_triggered(flags: u16) -> bool {
+    flags & TRIGGER_MASK == TRIGGER_LEVEL
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Madt {
+    pub local_apic_address: u32,
+    table: Sdt,
+}
+
+impl Madt {
+    pub fn entries(&self) -> MadtEntries {
+        let (start, end) = self.table.body();
+
+        MadtEntries {
+            // Skip the local APIC address and flags
+            addr: start + 8,
+            end,
+        }
+    }
+}
+
+pub struct MadtEntries {
+    addr: u32,
+    end: u32,
+}
+
+impl Iterator for MadtEntries {
+    type Item = MadtEntry;
+
+    fn next(&mut self) -> Option<MadtEntry> {
+        if self.addr + 2 > self.end {
+            return None;
+        }
+
+        let entry = self.addr;
+        let (kind, length) = unsafe { (*(entry as *const u8), *((entry + 1) as *const u8)) };
+
+        if length < 2 || entry + length as u32 > self.end {
+            return None;
+        }
+        self.addr += length as u32;
+
+        let read_u8 = |offset: u32| unsafe { *((entry + offset) as *const u8) };
+        let read_u16 = |offset: u32| unsafe { read_unaligned((entry + offset) as *const u16) };
+        let read_u32 = |offset: u32| unsafe { read_unaligned((entry + offset) as *const u32) };
+
+        Some(match kind {
+            ENTRY_LOCAL_APIC if length >= 8 => MadtEntry::LocalApic {
+                processor_id: read_u8(2),
+                apic_id: read_u8(3),
+                flags: read_u32(4),
+            },
+            ENTRY_IO_APIC if length >= 12 => MadtEntry::IoApic {
+                id: read_u8(2),
+                address: read_u32(4),
+                gsi_base: read_u32(8),
+            },
+            ENTRY_INTERRUPT_OVERRIDE if length >= 10 => MadtEntry::InterruptOverride {
+                bus: read_u8(2),
+                source: read_u8(3),
+                gsi: read_u32(4),
+                flags: read_u16(8),
+            },
+            _ => MadtEntry::Other(kind),
+        })
+    }
+}
+
+pub fn madt() -> Option<Madt> {
+    let table = find_table(b"APIC")?;
+    let (start, end) = table.body();
+
+    // The local APIC address is followed by flags
+    if start + 8 > end {
+        return None;
+    }
+
+    Some(Madt {
+        local_apic_address: unsafe { read_unaligned(start as *const u32) },
+        table,
+    })
+}
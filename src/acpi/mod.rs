@@ -0,0 +1,180 @@
+//! ACPI tables, found through the RSDP the bootloader passed in the Multiboot2 information.
+//! https://wiki.osdev.org/RSDT
+
+pub mod madt;
+
+use crate::interrupts::without_interrupts;
+use crate::memory::heap::{HEAP_MAX_SIZE, HEAP_START};
+use crate::memory::paging::{identity_map, map, PageFlags};
+use crate::memory::PAGE_SIZE;
+use crate::multiboot::boot_info;
+use core::mem::size_of;
+use core::ptr::read_unaligned;
+use spin::Mutex;
+
+/// Virtual pages for the tables that can't be identity mapped, right after the heap.
+const WINDOW_START: u32 = (HEAP_START + HEAP_MAX_SIZE) as u32;
+const WINDOW_END: u32 = WINDOW_START + 0x10_0000;
+const MAX_LOADED_TABLES: usize = 32;
+
+/// Header shared by every System Description Table.
+#[derive(Debug, Clone, Copy)]
+#[repr(C, packed)]
+pub struct SdtHeader {
+    pub signature: [u8; 4],
+    pub length: u32,
+    pub revision: u8,
+    pub checksum: u8,
+    pub oem_id: [u8; 6],
+    pub oem_table_id: [u8; 8],
+    pub oem_revision: u32,
+    pub creator_id: u32,
+    pub creator_revision: u32,
+}
+
+/// A table mapped in memory whose checksum has been verified.
+#[derive(Debug, Clone, Copy)]
+pub struct Sdt {
+    /// Virtual address the table is mapped at.
+    pub addr: u32,
+    pub header: SdtHeader,
+}
+
+/// Tables mapped so far, so that each one is mapped and checked only once.
+struct LoadedTables {
+    /// Physical address of each table, with the table or `None` if it is invalid.
+    tables: [Option<(u32, Option<Sdt>)>; MAX_LOADED_TABLES],
+    /// First unused page of the window.
+    window_next: u32,
+    /// First physical and virtual page of the latest window mapping, which grows when the same
+    /// table is mapped again with its full length.
+    window_last: Option<(u32, u32)>,
+}
+
+static LOADED_TABLES: Mutex<LoadedTables> = Mutex::new(LoadedTables {
+    tables: [None; MAX_LOADED_TABLES],
+    window_next: WINDOW_START,
+    window_last: None,
+});
+
+impl LoadedTables {
+    /// Maps the physical range `[start; end[` read-only, returns the virtual address of `start`.
+    ///
+    /// Tables are identity mapped, except those in the virtual range of the heap or of the
+    /// window which are mapped in the window instead.
+    fn map(&mut self, start: u32, end: u32) -> Option<u32> {
+        let first_page = start & !(PAGE_SIZE as u32 - 1);
+
+        if end <= HEAP_START as u32 || start >= WINDOW_END {
+            identity_map(start, end, PageFlags::PRESENT).ok()?;
+            return Some(start);
+        }
+
+        let base = match self.window_last {
+            Some((phys, virt)) if phys == first_page => virt,
+            _ => self.window_next,
+        };
+        let mut page = first_page;
+
+        while page < end {
+            let virt = base + (page - first_page);
+
+            if virt >= self.window_next {
+                if virt >= WINDOW_END {
+                    return None;
+                }
+                map(virt, page, PageFlags::PRESENT).ok()?;
+                self.window_next = virt + PAGE_SIZE as u32;
+            }
+            page = page.checked_add(PAGE_SIZE as u32)?;
+        }
+        self.window_last = Some((first_page, base));
+        Some(base + (start - first_page))
+    }
+
+    /// Maps the table at the physical address `addr` and checks it.
+    fn load(&mut self, addr: u32) -> Option<Sdt> {
+        let header_end = addr.checked_add(size_of::<SdtHeader>() as u32)?;
+        let virt = self.map(addr, header_end)?;
+
+        let header = unsafe { read_unaligned(virt as *const SdtHeader) };
+        let end = addr.checked_add(header.length)?;
+
+        if (header.length as usize) < size_of::<SdtHeader>() {
+            return None;
+        }
+        self.map(addr, end)?;
+
+        let bytes =
+            unsafe { core::slice::from_raw_parts(virt as *const u8, header.length as usize) };
+        let sum = bytes.iter().fold(0u8, |sum, &byte| sum.wrapping_add(byte));
+
+        match sum {
+            0 => Some(Sdt { addr: virt, header }),
+            _ => None,
+        }
+    }
+
+    /// Returns the table at the physical address `addr`, loading it the first time.
+    fn get(&mut self, addr: u32) -> Option<Sdt> {
+        let loaded = self
+            .tables
+            .iter()
+            .flatten()
+            .find(|&&(phys, _)| phys == addr);
+
+        if let Some(&(_, table)) = loaded {
+            return table;
+        }
+
+        let table = self.load(addr);
+        // Past the limit, tables are loaded again on every lookup
+        if let Some(slot) = self.tables.iter_mut().find(|slot| slot.is_none()) {
+            *slot = Some((addr, table));
+        }
+        table
+    }
+}
+
+impl Sdt {
+    /// Returns the table at the physical address `addr`, mapping and checking it the first time.
+    fn load(addr: u32) -> Option<Sdt> {
+        without_interrupts(|| LOADED_TABLES.lock().get(addr))
+    }
+
+    /// Bytes following the header.
+    pub fn body(&self) -> (u32, u32) {
+        (
+            self.addr + size_of::<SdtHeader>() as u32,
+            self.addr + self.header.length,
+        )
+    }
+}
+
+/// Looks for the table with `signature` in the XSDT, or the RSDT on ACPI 1.0 machines.
+pub fn find_table(signature: &[u8; 4]) -> Option<Sdt> {
+    let rsdp = boot_info()?.rsdp?;
+    // XSDT entries above 4 GiB can't be reached without PAE
+    let (root_addr, entry_size) = match rsdp.xsdt_address {
+        Some(xsdt) if xsdt <= u32::MAX as u64 => (xsdt as u32, 8),
+        _ => (rsdp.rsdt_address, 4),
+    };
+    let root = Sdt::load(root_addr)?;
+    let (start, end) = root.body();
+
+    (start..end)
+        .step_by(entry_size)
+        .filter(|entry| entry + entry_size as u32 <= end)
+        .filter_map(|entry| {
+            let addr = unsafe {
+                match entry_size {
+                    8 => read_unaligned(entry as *const u64),
+                    _ => read_unaligned(entry as *const u32) as u64,
+                }
+            };
+
+            u32::try_from(addr).ok()
+        })
+        .filter_map(Sdt::load)
+        .find(|table| &table.header.signature == signature)
+}
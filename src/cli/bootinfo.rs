@@ -0,0 +1,75 @@
+use crate::cli::CliState;
+use crate::multiboot::boot_info;
+use crate::println;
+
+pub fn bootinfo(_: &CliState) {
+    let info = match boot_info() {
+        Some(info) => info,
+        None => {
+            println!("No Multiboot2 boot information available");
+            return;
+        }
+    };
+
+    println!(
+        "Boot information at 0x{:08x} ({} bytes)",
+        info.start, info.total_size
+    );
+    println!("Bootloader: {}", info.bootloader_name.unwrap_or("unknown"));
+    println!("Command line: \"{}\"", info.command_line.unwrap_or_default());
+
+    if let Some((lower, upper)) = info.basic_memory {
+        println!("Memory: {} KiB lower, {} KiB upper", lower, upper);
+    }
+
+    if let Some(memory_map) = info.memory_map {
+        println!("Memory map:");
+        for entry in memory_map.iter() {
+            let (base, length) = (entry.base_addr, entry.length);
+
+            println!(
+                "  0x{:016x} - 0x{:016x} {:?}",
+                base,
+                base + length,
+                entry.kind()
+            );
+        }
+    }
+
+    for module in info.modules() {
+        println!(
+            "Module: 0x{:08x} - 0x{:08x} \"{}\"",
+            module.start, module.end, module.cmdline
+        );
+    }
+
+    if let Some(fb) = info.framebuffer {
+        println!(
+            "Framebuffer: 0x{:x} {}x{} {}bpp pitch {} {:?}",
+            fb.addr, fb.width, fb.height, fb.bpp, fb.pitch, fb.kind
+        );
+    }
+
+    if let Some(sections) = info.elf_sections {
+        println!("ELF sections:");
+        for section in sections.iter().filter(|s| s.addr != 0) {
+            println!(
+                "  {:<16} 0x{:08x} size 0x{:06x} flags 0x{:x}",
+                sections.name(&section),
+                section.addr,
+                section.size,
+                section.flags
+            );
+        }
+    }
+
+    if let Some(rsdp) = info.rsdp {
+        println!(
+            "ACPI RSDP: revision {} OEM \"{}\" RSDT 0x{:08x} XSDT {:x?}",
+            rsdp.revision,
+            crate::u8_to_str!(rsdp.oem_id),
+            rsdp.rsdt_address,
+            rsdp.xsdt_address
+        );
+    }
+}
@@ -1,6 +1,7 @@
-use crate::cli::CliState;
+use crate::cli::{CliState, COMMAND_LINE_LENGTH};
+use crate::interrupts::without_interrupts;
 use crate::panic::clean_registers;
-use crate::{asm, halt, println, stack_top, WRITER};
+use crate::{asm, halt, print, println, stack_top, WRITER};
 
 pub fn unknown_command(cli_state: &CliState) {
     let (_, mut argv) = crate::split_u8_string!(cli_state.command_line);
@@ -17,16 +18,27 @@ pub fn help(_: &CliState) {
     println!("- hexdump <addr?> <size?>: Hexdump the memory at the given address for a given number of bytes");
     println!("- divide_by_zero: raise divide_by_zero exception");
     println!("- int <hexcode>: call an corresponding isr");
+    println!("- bootinfo: Display the Multiboot2 boot information");
+    println!("- mem: Display physical memory and heap usage");
+    println!("- gdt: Decode the Global Descriptor Table");
+    println!("- idt: Decode the Interrupt Descriptor Table");
+    println!("- irqstat: Display how many times each interrupt vector was raised");
+    println!("- uptime: Display the time elapsed since boot");
+    println!("- sleep <ms>: Wait for the given number of milliseconds");
+    println!("- date [YYYY-MM-DD HH:MM:SS]: Display or set the date and time");
+    println!("- bench <command>: Run a command and display how long it took");
     println!("- clear: Clear the console");
     println!("- exit: Exit the kernel");
 }
 
 pub fn clear(_: &CliState) {
-    let mut writer = WRITER.lock();
+    without_interrupts(|| {
+        let mut writer = WRITER.lock();
 
-    for i in 0..25 {
-        writer.clear_row(i);
-    }
+        for i in 0..25 {
+            writer.clear_row(i);
+        }
+    });
 }
 
 pub fn exit(_: &CliState) {
@@ -45,7 +57,7 @@ pub fn echo(cli_state: &CliState) {
 }
 
 pub fn keymap(cli_state: &CliState) {
-    use crate::interrupts::isr::KEYBOARD_STATE;
+    use crate::keyboard::KEYBOARD_STATE;
 
     let (argc, mut argv) = crate::split_u8_string!(cli_state.command_line);
     let usage = || println!("Usage: keymap <us|fr>");
@@ -85,3 +97,144 @@ pub fn hexdump(cli_state: &CliState) {
 pub fn divide_by_zero(_: &CliState) {
     unsafe { asm!("xor edx, edx", "mov eax, 0x42", "xor ecx, ecx", "div ecx") }
 }
+
+pub fn mem(_: &CliState) {
+    use crate::memory::{frame_allocator::FRAME_ALLOCATOR, heap, PAGE_SIZE};
+
+    let stats = FRAME_ALLOCATOR.lock().stats();
+    let heap_stats = heap::stats();
+    let to_kib = |frames: usize| frames * PAGE_SIZE / 1024;
+
+    println!("Total frames: {} ({} KiB)", stats.total, to_kib(stats.total));
+    println!("Used frames: {} ({} KiB)", stats.used, to_kib(stats.used));
+    println!("Free frames: {} ({} KiB)", stats.free, to_kib(stats.free));
+    println!(
+        "Heap: {} KiB mapped, {} bytes used, {} bytes free",
+        heap_stats.size / 1024,
+        heap_stats.used,
+        heap_stats.free
+    );
+}
+
+pub fn gdt(_: &CliState) {
+    crate::gdt::print();
+}
+
+pub fn idt(_: &CliState) {
+    crate::interrupts::idt::print();
+}
+
+pub fn irqstat(_: &CliState) {
+    use crate::interrupts::irq::{controller, irq_vector, InterruptController, IRQ_COUNT};
+    use crate::interrupts::{apic, interrupt_count, pic8259::PICS, InterruptIndex};
+
+    println!("Vector  Count       Name");
+    for vector in 0..=u8::MAX {
+        let count = interrupt_count(vector);
+
+        if count == 0 {
+            continue;
+        }
+
+        print!("{:#04x}    {:<10}  ", vector, count);
+        match InterruptIndex::from_vector(vector) {
+            Some(index) => println!("{:?}", index),
+            None if vector == apic::SPURIOUS_VECTOR => println!("APIC spurious"),
+            None => match vector.checked_sub(irq_vector(0)) {
+                Some(irq) if irq < IRQ_COUNT => println!("IRQ {}", irq),
+                _ => println!("-"),
+            },
+        }
+    }
+
+    if controller() == InterruptController::Pic8259 {
+        // The IRQ dispatcher locks the PICs too
+        let [master, slave] = without_interrupts(|| PICS.lock().spurious_interrupts());
+
+        println!(
+            "Spurious PIC interrupts: {} on IRQ 7, {} on IRQ 15",
+            master, slave
+        );
+    }
+}
+
+pub fn uptime(_: &CliState) {
+    use crate::time::pit;
+
+    let uptime = crate::time::uptime();
+    let seconds = uptime.as_secs();
+
+    println!(
+        "Up {}:{:02}:{:02}.{:03} ({} ticks at {} Hz)",
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60,
+        uptime.subsec_millis(),
+        pit::ticks(),
+        pit::frequency()
+    );
+}
+
+pub fn sleep(cli_state: &CliState) {
+    let (argc, mut argv) = crate::split_u8_string!(cli_state.command_line);
+    let milliseconds = argv.nth(1).and_then(|arg| arg.parse::<u64>().ok());
+
+    match milliseconds {
+        Some(milliseconds) if argc == 2 => crate::time::timer::sleep_ms(milliseconds),
+        _ => println!("Usage: sleep <ms>"),
+    }
+}
+
+pub fn date(cli_state: &CliState) {
+    use crate::time::rtc::{self, DateTime};
+
+    let (argc, mut argv) = crate::split_u8_string!(cli_state.command_line);
+
+    match argc {
+        1 => println!("{}", rtc::read()),
+        3 => {
+            let (date, time) = (argv.nth(1).unwrap_or(""), argv.next().unwrap_or(""));
+
+            match DateTime::parse(date, time) {
+                Some(date_time) => {
+                    if let Err(err) = rtc::write(&date_time) {
+                        println!("Failed to set the date: {:?}", err);
+                    }
+                }
+                None => println!("Invalid date, expected YYYY-MM-DD HH:MM:SS"),
+            }
+        }
+        _ => println!("Usage: date [YYYY-MM-DD HH:MM:SS]"),
+    }
+}
+
+pub fn bench(cli_state: &CliState) {
+    let command_line = crate::u8_to_str!(cli_state.command_line);
+    let command = command_line
+        .trim_start()
+        .strip_prefix("bench")
+        .unwrap_or("")
+        .trim();
+
+    if command.is_empty() {
+        println!("Usage: bench <command>");
+        return;
+    }
+
+    let mut bench_state = CliState {
+        command_line: [b'\0'; COMMAND_LINE_LENGTH],
+        caret_blink: cli_state.caret_blink,
+    };
+    bench_state.command_line[..command.len()].copy_from_slice(command.as_bytes());
+
+    let start = crate::time::now();
+    super::call_cli_handler(&bench_state);
+    let elapsed = crate::time::now() - start;
+
+    println!(
+        "\"{}\" took {}.{:06} ms",
+        command,
+        elapsed.as_secs() * 1000 + elapsed.subsec_millis() as u64,
+        elapsed.subsec_nanos() % 1_000_000
+    );
+}
@@ -1,7 +1,19 @@
+use crate::interrupts::exception::catch_faults;
+use crate::interrupts::without_interrupts;
+use crate::keyboard::{handle_scancode, KeyEvent, KEYBOARD_STATE, SCANCODES};
+use crate::serial::{self, is_console_mirrored};
+use crate::time::{timer, Duration};
 use crate::{println, WRITER};
-use commands::{clear, divide_by_zero, echo, exit, help, hexdump, keymap, unknown_command};
+use bootinfo::bootinfo;
+use commands::{
+    bench, clear, date, divide_by_zero, echo, exit, gdt, help, hexdump, idt, irqstat, keymap, mem,
+    sleep, unknown_command, uptime,
+};
+use core::arch::asm;
+use core::sync::atomic::{AtomicBool, Ordering};
 use int::interrupt;
 
+mod bootinfo;
 mod commands;
 mod int;
 
@@ -9,6 +21,8 @@ pub const COMMAND_LINE_LENGTH: usize = crate::vga_buffer::BUFFER_WIDTH - PS1.len
 const ASCII_BACKSPACE: u8 = 0x08;
 const ASCII_DELETE: u8 = 0x7f;
 const PS1: &str = "> ";
+/// Time between two caret blinks.
+const CARET_BLINK_PERIOD: Duration = Duration::from_millis(500);
 
 type Handler = unsafe fn(_: &CliState) -> ();
 
@@ -21,6 +35,15 @@ const HANDLERS: &[(&str, Handler)] = &[
     ("exit", exit),
     ("divide_by_zero", divide_by_zero),
     ("int", interrupt),
+    ("bootinfo", bootinfo),
+    ("mem", mem),
+    ("gdt", gdt),
+    ("idt", idt),
+    ("irqstat", irqstat),
+    ("uptime", uptime),
+    ("sleep", sleep),
+    ("date", date),
+    ("bench", bench),
 ];
 
 pub struct CliState {
@@ -28,6 +51,14 @@ pub struct CliState {
     pub caret_blink: bool,
 }
 
+pub static mut CLI_STATE: CliState = CliState {
+    command_line: [b'\0'; COMMAND_LINE_LENGTH],
+    caret_blink: false,
+};
+
+/// Set by the timer interrupt, the main loop blinks the caret once no command is running.
+static CARET_BLINK_DUE: AtomicBool = AtomicBool::new(false);
+
 fn get_handler(command_name: &str) -> Handler {
     for &(handler_name, handler_func) in HANDLERS {
         if handler_name == command_name {
@@ -41,20 +72,44 @@ fn call_cli_handler(cli_state: &CliState) {
     let (_, mut argv) = crate::split_u8_string!(cli_state.command_line);
 
     if let Some(command_name) = argv.next() {
-        unsafe {
-            get_handler(command_name)(cli_state);
+        let handler = get_handler(command_name);
+
+        if let Err(fault) = catch_faults(|| unsafe { handler(cli_state) }) {
+            println!(
+                "Command \"{}\" aborted by {:?} exception at 0x{:08x} (error code {:#x})",
+                command_name, fault.exception, fault.instruction_pointer, fault.error_code
+            );
         }
     }
 }
 
+/// Echoes the command line edits on the serial console, which can't be redrawn like the screen.
+fn echo_serial(bytes: &[u8]) {
+    if is_console_mirrored() {
+        serial::write_bytes(bytes);
+    }
+}
+
+/// Draws the prompt and starts blinking the caret.
+pub fn init() {
+    echo_serial(PS1.as_bytes());
+    handle_cli_change(unsafe { &mut CLI_STATE }, "");
+    timer::add_periodic(CARET_BLINK_PERIOD, blink_caret);
+}
+
+fn blink_caret() {
+    CARET_BLINK_DUE.store(true, Ordering::SeqCst);
+}
+
 pub fn handle_cli_caret_blink(cli_state: &mut CliState) {
+    without_interrupts(|| draw_caret(cli_state));
+    cli_state.caret_blink = !cli_state.caret_blink;
+}
+
+fn draw_caret(cli_state: &CliState) {
     let mut writer = WRITER.lock();
-    let position = cli_state
-        .command_line
-        .iter()
-        .position(|&c| c == b'\0')
-        .unwrap_or(0)
-        + PS1.len();
+    // A character takes one cell whatever the length of its UTF-8 encoding
+    let position = crate::u8_to_str!(cli_state.command_line).chars().count() + PS1.len();
 
     writer.column_position = position;
 
@@ -70,20 +125,21 @@ pub fn handle_cli_caret_blink(cli_state: &mut CliState) {
     if cli_state.caret_blink {
         writer.set_colors(Some(new_background), Some(new_foreground));
     }
-
-    cli_state.caret_blink = !cli_state.caret_blink;
 }
 
 fn write_command_line(cli_state: &CliState) {
-    let mut writer = WRITER.lock();
-    let command_line_index = crate::get_array_end_index!(cli_state.command_line);
+    // Interrupt handlers may print, which also locks the writer
+    without_interrupts(|| {
+        let mut writer = WRITER.lock();
+        let command_line = crate::u8_to_str!(cli_state.command_line);
 
-    writer.column_position = 0;
-    writer.write_string(PS1);
-    writer.write_string(&crate::u8_to_str!(cli_state.command_line));
-    for _ in 0..COMMAND_LINE_LENGTH - command_line_index {
-        writer.write_byte(b' ');
-    }
+        writer.column_position = 0;
+        writer.write_string(PS1);
+        writer.write_string(command_line);
+        for _ in 0..COMMAND_LINE_LENGTH - command_line.chars().count() {
+            writer.write_byte(b' ');
+        }
+    });
 }
 
 pub fn handle_cli_change(cli_state: &mut CliState, change_str: &str) {
@@ -92,26 +148,124 @@ pub fn handle_cli_change(cli_state: &mut CliState, change_str: &str) {
 
         call_cli_handler(cli_state);
         cli_state.command_line = [b'\0'; COMMAND_LINE_LENGTH];
+        echo_serial(PS1.as_bytes());
         return;
     }
 
     let mut command_line_index = crate::get_array_end_index!(cli_state.command_line);
 
-    for c in change_str.bytes() {
-        if c == ASCII_BACKSPACE || c == ASCII_DELETE {
-            if command_line_index > 0 {
-                command_line_index -= 1;
-                cli_state.command_line[command_line_index] = b'\0';
+    // The command line is kept valid UTF-8, characters are added and removed whole
+    for c in change_str.chars() {
+        if c == ASCII_BACKSPACE as char || c == ASCII_DELETE as char {
+            let last = crate::u8_to_str!(cli_state.command_line)
+                .chars()
+                .next_back();
+
+            if let Some(last) = last {
+                let start = command_line_index - last.len_utf8();
+
+                cli_state.command_line[start..command_line_index].fill(b'\0');
+                command_line_index = start;
+                echo_serial(b"\x08 \x08");
             }
             continue;
         }
 
-        if command_line_index >= COMMAND_LINE_LENGTH - 1 {
+        let end = command_line_index + c.len_utf8();
+        if end > COMMAND_LINE_LENGTH - 1 {
             continue;
         }
 
-        cli_state.command_line[command_line_index] = c;
-        command_line_index += 1;
+        c.encode_utf8(&mut cli_state.command_line[command_line_index..end]);
+        echo_serial(&cli_state.command_line[command_line_index..end]);
+        command_line_index = end;
     }
     write_command_line(cli_state);
 }
+
+/// Abandons the command line being typed, like a shell does on Ctrl+C.
+fn cancel_command_line(cli_state: &mut CliState) {
+    handle_cli_change(cli_state, "^C");
+    println!();
+
+    cli_state.command_line = [b'\0'; COMMAND_LINE_LENGTH];
+    echo_serial(PS1.as_bytes());
+}
+
+pub fn handle_key_event(cli_state: &mut CliState, event: &KeyEvent) {
+    if !event.pressed {
+        return;
+    }
+
+    match event.char {
+        Some(c) if event.modifiers.ctrl => {
+            if c.eq_ignore_ascii_case(&'c') {
+                cancel_command_line(cli_state);
+            }
+        }
+        Some(c) => {
+            if let Some(accent) = event.accent {
+                handle_cli_change(cli_state, accent.encode_utf8(&mut [0; 4]));
+            }
+            handle_cli_change(cli_state, c.encode_utf8(&mut [0; 4]));
+        }
+        None => {}
+    }
+}
+
+/// Interprets a byte received from the serial console. `after_cr` tells whether the previous
+/// byte was a carriage return.
+fn handle_serial_byte(cli_state: &mut CliState, byte: u8, after_cr: bool) {
+    let change = match byte {
+        // Terminals send CR for Enter, pipes send LF, and some send both
+        b'\r' => "\n",
+        b'\n' if after_cr => return,
+        b'\n' => "\n",
+        0x08 | 0x7F => "\x7F",
+        0x20..=0x7E => match core::str::from_utf8(core::slice::from_ref(&byte)) {
+            Ok(change) => change,
+            Err(_) => return,
+        },
+        _ => return,
+    };
+
+    handle_cli_change(cli_state, change);
+}
+
+/// Kernel main loop: runs the shell on the input queued by the keyboard and serial interrupts,
+/// blinks the caret when the timer asks for it, and halts while there is nothing to do.
+pub fn run() -> ! {
+    let cli_state = unsafe { &mut CLI_STATE };
+    let mut after_cr = false;
+
+    loop {
+        while let Some(scancode) = SCANCODES.pop() {
+            if let Some(event) = handle_scancode(scancode, unsafe { &mut KEYBOARD_STATE }) {
+                handle_key_event(cli_state, &event);
+            }
+        }
+
+        while let Some(byte) = serial::RECEIVED.pop() {
+            handle_serial_byte(cli_state, byte, after_cr);
+            after_cr = byte == b'\r';
+        }
+
+        // Only drawn here, a command running would have its output overwritten
+        if CARET_BLINK_DUE.swap(false, Ordering::SeqCst) {
+            handle_cli_caret_blink(cli_state);
+        }
+
+        unsafe {
+            asm!("cli");
+            // `sti` takes effect after `hlt` started, input queued since the check wakes it up
+            if SCANCODES.is_empty()
+                && serial::RECEIVED.is_empty()
+                && !CARET_BLINK_DUE.load(Ordering::SeqCst)
+            {
+                asm!("sti", "hlt");
+            } else {
+                asm!("sti");
+            }
+        }
+    }
+}
@@ -0,0 +1,354 @@
+//! Segment and gate descriptors of the GDT, and the selectors referring to them.
+//! https://wiki.osdev.org/Global_Descriptor_Table
+
+use core::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum PrivilegeLevel {
+    Ring0 = 0,
+    Ring1 = 1,
+    Ring2 = 2,
+    Ring3 = 3,
+}
+
+impl PrivilegeLevel {
+    pub fn from_bits(bits: u8) -> Self {
+        match bits & 0b11 {
+            0 => PrivilegeLevel::Ring0,
+            1 => PrivilegeLevel::Ring1,
+            2 => PrivilegeLevel::Ring2,
+            _ => PrivilegeLevel::Ring3,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum TableIndicator {
+    Gdt = 0,
+    Ldt = 1,
+}
+
+/// Index of a descriptor in the GDT or an LDT, with the requested privilege level.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(transparent)]
+pub struct SegmentSelector(u16);
+
+impl SegmentSelector {
+    pub const fn new(index: u16, table: TableIndicator, rpl: PrivilegeLevel) -> Self {
+        SegmentSelector(index << 3 | (table as u16) << 2 | rpl as u16)
+    }
+
+    pub const fn bits(self) -> u16 {
+        self.0
+    }
+
+    #[allow(dead_code)]
+    pub fn index(self) -> u16 {
+        self.0 >> 3
+    }
+
+    #[allow(dead_code)]
+    pub fn table(self) -> TableIndicator {
+        match self.0 & (1 << 2) {
+            0 => TableIndicator::Gdt,
+            _ => TableIndicator::Ldt,
+        }
+    }
+
+    #[allow(dead_code)]
+    pub fn rpl(self) -> PrivilegeLevel {
+        PrivilegeLevel::from_bits(self.0 as u8)
+    }
+}
+
+/// System descriptor types valid in 32-bit protected mode.
+#[allow(dead_code)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum SystemType {
+    Ldt = 0x2,
+    TaskGate = 0x5,
+    AvailableTss = 0x9,
+    BusyTss = 0xB,
+    CallGate = 0xC,
+}
+
+impl SystemType {
+    pub fn from_bits(bits: u8) -> Option<Self> {
+        match bits & 0xF {
+            0x2 => Some(SystemType::Ldt),
+            0x5 => Some(SystemType::TaskGate),
+            0x9 => Some(SystemType::AvailableTss),
+            0xB => Some(SystemType::BusyTss),
+            0xC => Some(SystemType::CallGate),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DescriptorType {
+    /// Conforming code can be called from a lower privilege level without a gate.
+    Code {
+        conforming: bool,
+        readable: bool,
+    },
+    /// Expand-down data segments are valid above their limit, as stacks growing down.
+    Data {
+        expand_down: bool,
+        writable: bool,
+    },
+    System(SystemType),
+}
+
+impl DescriptorType {
+    fn bits(self) -> u8 {
+        // The S bit (4) tells code and data segments from system descriptors
+        match self {
+            DescriptorType::Code {
+                conforming,
+                readable,
+            } => 0b11000 | (conforming as u8) << 2 | (readable as u8) << 1,
+            DescriptorType::Data {
+                expand_down,
+                writable,
+            } => 0b10000 | (expand_down as u8) << 2 | (writable as u8) << 1,
+            DescriptorType::System(kind) => kind as u8,
+        }
+    }
+
+    /// Decodes the lower 5 bits of an access byte, `None` for reserved system types.
+    pub fn from_bits(bits: u8) -> Option<Self> {
+        let flag = |bit: u8| bits & (1 << bit) != 0;
+
+        match (flag(4), flag(3)) {
+            (true, true) => Some(DescriptorType::Code {
+                conforming: flag(2),
+                readable: flag(1),
+            }),
+            (true, false) => Some(DescriptorType::Data {
+                expand_down: flag(2),
+                writable: flag(1),
+            }),
+            (false, _) => SystemType::from_bits(bits).map(DescriptorType::System),
+        }
+    }
+}
+
+impl fmt::Display for DescriptorType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            DescriptorType::Code {
+                conforming,
+                readable,
+            } => {
+                write!(f, "code")?;
+                if conforming {
+                    write!(f, " conforming")?;
+                }
+                match readable {
+                    true => write!(f, " readable"),
+                    false => write!(f, " execute-only"),
+                }
+            }
+            DescriptorType::Data {
+                expand_down,
+                writable,
+            } => {
+                write!(f, "data")?;
+                if expand_down {
+                    write!(f, " expand-down")?;
+                }
+                match writable {
+                    true => write!(f, " writable"),
+                    false => write!(f, " read-only"),
+                }
+            }
+            DescriptorType::System(kind) => write!(f, "{:?}", kind),
+        }
+    }
+}
+
+/// Access byte: present bit, descriptor privilege level and type.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Access {
+    pub present: bool,
+    pub dpl: PrivilegeLevel,
+    pub kind: DescriptorType,
+}
+
+impl Access {
+    pub const fn new(kind: DescriptorType, dpl: PrivilegeLevel) -> Self {
+        Access {
+            present: true,
+            dpl,
+            kind,
+        }
+    }
+
+    pub fn bits(self) -> u8 {
+        (self.present as u8) << 7 | (self.dpl as u8) << 5 | self.kind.bits()
+    }
+
+    pub fn from_bits(bits: u8) -> Option<Self> {
+        Some(Access {
+            present: bits & (1 << 7) != 0,
+            dpl: PrivilegeLevel::from_bits(bits >> 5),
+            kind: DescriptorType::from_bits(bits)?,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum Granularity {
+    Byte = 0,
+    /// The limit is counted in 4 KiB pages.
+    Page = 1 << 7,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum OperandSize {
+    Bits16 = 0,
+    Bits32 = 1 << 6,
+}
+
+/// Upper nibble of the granularity byte.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Flags {
+    pub granularity: Granularity,
+    pub size: OperandSize,
+}
+
+impl Flags {
+    /// 32-bit segment whose limit is counted in pages, to cover the whole address space.
+    pub const FLAT: Flags = Flags {
+        granularity: Granularity::Page,
+        size: OperandSize::Bits32,
+    };
+    /// Limit counted in bytes, as used by system segments.
+    pub const SYSTEM: Flags = Flags {
+        granularity: Granularity::Byte,
+        size: OperandSize::Bits16,
+    };
+
+    pub fn bits(self) -> u8 {
+        self.granularity as u8 | self.size as u8
+    }
+
+    pub fn from_bits(bits: u8) -> Self {
+        Flags {
+            granularity: match bits & Granularity::Page as u8 {
+                0 => Granularity::Byte,
+                _ => Granularity::Page,
+            },
+            size: match bits & OperandSize::Bits32 as u8 {
+                0 => OperandSize::Bits16,
+                _ => OperandSize::Bits32,
+            },
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+#[repr(C, packed)]
+pub struct SegmentDescriptor {
+    limit_low: u16,  // Limite 0-15
+    base_low: u16,   // Base 0-15
+    base_middle: u8, // Base 16-23
+    access: u8,      // Type, S, DPL, P
+    granularity: u8, // L, DB, G, Limit 16-19
+    base_high: u8,   // Base 24-31
+}
+
+impl SegmentDescriptor {
+    pub const NULL: SegmentDescriptor = SegmentDescriptor {
+        limit_low: 0,
+        base_low: 0,
+        base_middle: 0,
+        access: 0,
+        granularity: 0,
+        base_high: 0,
+    };
+
+    pub fn new(base: u32, limit: u32, access: Access, flags: Flags) -> Self {
+        SegmentDescriptor {
+            base_low: base as u16,
+            base_middle: (base >> 16) as u8,
+            base_high: (base >> 24) as u8,
+            limit_low: limit as u16,
+            granularity: ((limit >> 16) as u8 & 0x0F) | flags.bits(),
+            access: access.bits(),
+        }
+    }
+
+    /// Gate to `offset` in the code segment `target`, copying `parameters` dwords from the
+    /// caller's stack when the privilege level changes.
+    pub fn call_gate(
+        target: SegmentSelector,
+        offset: u32,
+        dpl: PrivilegeLevel,
+        parameters: u8,
+    ) -> Self {
+        let access = Access::new(DescriptorType::System(SystemType::CallGate), dpl);
+
+        // Offset split between the low and the last two bytes, selector where the base starts
+        SegmentDescriptor {
+            limit_low: offset as u16,
+            base_low: target.bits(),
+            base_middle: parameters & 0x1F,
+            access: access.bits(),
+            granularity: (offset >> 16) as u8,
+            base_high: (offset >> 24) as u8,
+        }
+    }
+
+    pub fn dpl(&self) -> PrivilegeLevel {
+        PrivilegeLevel::from_bits(self.access >> 5)
+    }
+
+    pub fn is_null(&self) -> bool {
+        self.access == 0 && self.base() == 0 && self.limit() == 0
+    }
+
+    pub fn base(&self) -> u32 {
+        self.base_low as u32 | (self.base_middle as u32) << 16 | (self.base_high as u32) << 24
+    }
+
+    /// Raw 20-bit limit, in pages when the granularity flag is set.
+    pub fn limit(&self) -> u32 {
+        self.limit_low as u32 | (self.granularity as u32 & 0x0F) << 16
+    }
+
+    /// Offset of the last addressable byte.
+    pub fn byte_limit(&self) -> u32 {
+        match self.flags().granularity {
+            Granularity::Byte => self.limit(),
+            Granularity::Page => self.limit() << 12 | 0xFFF,
+        }
+    }
+
+    /// `None` when the type is reserved.
+    pub fn access(&self) -> Option<Access> {
+        Access::from_bits(self.access)
+    }
+
+    pub fn flags(&self) -> Flags {
+        Flags::from_bits(self.granularity)
+    }
+
+    /// Destination of a call gate, see `call_gate`.
+    pub fn gate_target(&self) -> (SegmentSelector, u32) {
+        let offset =
+            self.limit_low as u32 | (self.granularity as u32) << 16 | (self.base_high as u32) << 24;
+
+        (SegmentSelector(self.base_low), offset)
+    }
+
+    pub fn gate_parameters(&self) -> u8 {
+        self.base_middle & 0x1F
+    }
+}
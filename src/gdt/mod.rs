@@ -1,109 +1,328 @@
-use crate::vga_buffer::hexdump;
+pub mod descriptor;
+pub mod tss;
+
+use crate::println;
 use core::arch::asm;
-use core::ptr::copy_nonoverlapping;
+use descriptor::{
+    Access, DescriptorType, Flags, PrivilegeLevel, SegmentDescriptor, SegmentSelector, SystemType,
+    TableIndicator,
+};
+use spin::Mutex;
 
-#[repr(C, packed)]
-struct SegmentDescriptor {
-    limit_low: u16,  // Limite 0-15
-    base_low: u16,   // Base 0-15
-    base_middle: u8, // Base 16-23
-    access: u8,      // Type, S, DPL, P
-    granularity: u8, // L, DB, G, Limit 16-19
-    base_high: u8,   // Base 24-31
-}
+/// Descriptors can be added at runtime until the table is full.
+const GDT_ENTRIES: usize = 32;
 
-impl SegmentDescriptor {
-    fn new(base: u32, limit: u32, access: u8, granularity: u8) -> Self {
-        SegmentDescriptor {
-            base_low: base as u16,
-            base_middle: (base >> 16) as u8,
-            base_high: (base >> 24) as u8,
-            limit_low: limit as u16,
-            granularity: ((limit >> 16) as u8 & 0x0F) | (granularity & 0xF0),
-            access: access,
-        }
-    }
+// `init` lays out the kernel descriptors so that they get these selectors
+pub const KERNEL_CODE_SELECTOR: SegmentSelector =
+    SegmentSelector::new(1, TableIndicator::Gdt, PrivilegeLevel::Ring0);
+pub const KERNEL_DATA_SELECTOR: SegmentSelector =
+    SegmentSelector::new(2, TableIndicator::Gdt, PrivilegeLevel::Ring0);
+pub const KERNEL_TSS_SELECTOR: SegmentSelector =
+    SegmentSelector::new(7, TableIndicator::Gdt, PrivilegeLevel::Ring0);
+pub const DOUBLE_FAULT_TSS_SELECTOR: SegmentSelector =
+    SegmentSelector::new(8, TableIndicator::Gdt, PrivilegeLevel::Ring0);
+
+/// Table loaded in GDTR, it lives in the kernel image and never moves once installed.
+#[derive(Clone, Copy)]
+#[repr(C, align(8))]
+pub struct GlobalDescriptorTable {
+    entries: [SegmentDescriptor; GDT_ENTRIES],
+    len: usize,
 }
 
+pub static GDT: Mutex<GlobalDescriptorTable> = Mutex::new(GlobalDescriptorTable::new());
+
 #[repr(C, packed)]
-pub struct GlobalDescriptorTable {
-    null_segment: SegmentDescriptor,
-    code_segment: SegmentDescriptor,
-    data_segment: SegmentDescriptor,
-    stack_segment: SegmentDescriptor, // Descripteur de segment null, toujours 0
-    user_code_segment: SegmentDescriptor,
-    user_data_segment: SegmentDescriptor,
-    user_stack_segment: SegmentDescriptor,
+#[derive(Debug)]
+pub struct GdtPointer {
+    pub limit: u16,
+    pub base: u32,
 }
 
 impl GlobalDescriptorTable {
-    pub fn init() -> Self {
+    const fn new() -> Self {
         GlobalDescriptorTable {
-            null_segment: SegmentDescriptor::new(0, 0, 0, 0),
-            code_segment: SegmentDescriptor::new(0, 0xFFFFF, 0b10011010, 0b11001111),
-            data_segment: SegmentDescriptor::new(0, 0xFFFFF, 0b10010010, 0b11001111),
-            stack_segment: SegmentDescriptor::new(0, 0xFFFFF, 0b10010111, 0b11001111),
-            user_code_segment: SegmentDescriptor::new(0, 0xFFFFF, 0xFA, 0b11001111),
-            user_data_segment: SegmentDescriptor::new(0, 0xFFFFF, 0xF2, 0b11001111),
-            user_stack_segment: SegmentDescriptor::new(0, 0xFFFFF, 0xF2, 0b11001111),
+            entries: [SegmentDescriptor::NULL; GDT_ENTRIES],
+            // The first entry is the null descriptor
+            len: 1,
+        }
+    }
+
+    /// Appends `descriptor`, the selector requesting its privilege level.
+    fn push(&mut self, descriptor: SegmentDescriptor) -> Option<SegmentSelector> {
+        if self.len == GDT_ENTRIES {
+            return None;
+        }
+
+        let index = self.len;
+
+        self.entries[index] = descriptor;
+        self.len += 1;
+        Some(SegmentSelector::new(
+            index as u16,
+            TableIndicator::Gdt,
+            descriptor.dpl(),
+        ))
+    }
+
+    fn pointer(&self) -> GdtPointer {
+        GdtPointer {
+            limit: (self.len * core::mem::size_of::<SegmentDescriptor>() - 1) as u16,
+            base: self.entries.as_ptr() as u32,
+        }
+    }
+
+    /// Loads GDTR, which must be done again each time the table grows.
+    fn load(&self) {
+        let gdt_pointer = self.pointer();
+
+        unsafe {
+            asm!("lgdt [{x}]", x = in(reg) &gdt_pointer);
         }
     }
 }
 
-#[repr(C, packed)]
-#[derive(Debug)]
-pub struct GdtPointer {
-    limit: u16,
-    base: u32,
+/// Lays out a GDT before installing it, every `add_*` returning the selector of the new
+/// descriptor.
+pub struct GdtBuilder {
+    table: GlobalDescriptorTable,
 }
 
-impl GlobalDescriptorTable {
-    pub fn install(&self) {
-        let gdt_pointer = GdtPointer {
-            limit: (core::mem::size_of::<GlobalDescriptorTable>() - 1) as u16,
-            base: 0x800,
-        };
+impl GdtBuilder {
+    pub fn new() -> Self {
+        GdtBuilder {
+            table: GlobalDescriptorTable::new(),
+        }
+    }
 
-        unsafe {
-            copy_nonoverlapping(
-                self as *const _ as *mut u8,
-                0x800 as *mut u8,
-                gdt_pointer.limit as usize + 1,
-            );
+    pub fn add(&mut self, descriptor: SegmentDescriptor) -> SegmentSelector {
+        match self.table.push(descriptor) {
+            Some(selector) => selector,
+            None => panic!("The GDT is limited to {} descriptors", GDT_ENTRIES),
         }
+    }
+
+    /// Adds a segment covering the whole address space.
+    pub fn add_flat_segment(
+        &mut self,
+        kind: DescriptorType,
+        dpl: PrivilegeLevel,
+    ) -> SegmentSelector {
+        self.add(SegmentDescriptor::new(
+            0,
+            0xFFFFF,
+            Access::new(kind, dpl),
+            Flags::FLAT,
+        ))
+    }
+
+    pub fn add_code_segment(&mut self, dpl: PrivilegeLevel) -> SegmentSelector {
+        let kind = DescriptorType::Code {
+            conforming: false,
+            readable: true,
+        };
+
+        self.add_flat_segment(kind, dpl)
+    }
+
+    pub fn add_data_segment(&mut self, dpl: PrivilegeLevel) -> SegmentSelector {
+        let kind = DescriptorType::Data {
+            expand_down: false,
+            writable: true,
+        };
+
+        self.add_flat_segment(kind, dpl)
+    }
+
+    /// Adds an available 32-bit TSS, see `tss`.
+    pub fn add_tss(&mut self, tss_addr: u32) -> SegmentSelector {
+        let access = Access::new(
+            DescriptorType::System(SystemType::AvailableTss),
+            PrivilegeLevel::Ring0,
+        );
+
+        self.add(SegmentDescriptor::new(
+            tss_addr,
+            tss::TSS_LIMIT,
+            access,
+            Flags::SYSTEM,
+        ))
+    }
+
+    /// Adds a Local Descriptor Table holding `entries` descriptors.
+    #[allow(dead_code)]
+    pub fn add_ldt(&mut self, ldt_addr: u32, entries: usize) -> SegmentSelector {
+        let access = Access::new(
+            DescriptorType::System(SystemType::Ldt),
+            PrivilegeLevel::Ring0,
+        );
+        let limit = (entries * core::mem::size_of::<SegmentDescriptor>() - 1) as u32;
+
+        self.add(SegmentDescriptor::new(
+            ldt_addr,
+            limit,
+            access,
+            Flags::SYSTEM,
+        ))
+    }
+
+    #[allow(dead_code)]
+    pub fn add_call_gate(
+        &mut self,
+        target: SegmentSelector,
+        offset: u32,
+        dpl: PrivilegeLevel,
+        parameters: u8,
+    ) -> SegmentSelector {
+        self.add(SegmentDescriptor::call_gate(
+            target, offset, dpl, parameters,
+        ))
+    }
+
+    /// Replaces the live GDT with the built one and reloads the segment registers with `code`
+    /// and `data`.
+    pub fn install(self, code: SegmentSelector, data: SegmentSelector) {
+        let mut gdt = GDT.lock();
+
+        *gdt = self.table;
+        gdt.load();
 
         unsafe {
             asm!(
-                "lgdt ({x})",
-                "ljmp $0x08, $42f",
+                // Far return, as ljmp only takes an immediate selector
+                "pushl {code}",
+                "pushl $42f",
+                "lretl",
                 "42:",
-                "movw $0x10, %ax",
-                "movw %ax, %ds",
-                "movw %ax, %es",
-                "movw %ax, %fs",
-                "movw %ax, %gs",
-                "movw %ax, %ss",
-                x = in(reg) &gdt_pointer,
+                "movw {data:x}, %ds",
+                "movw {data:x}, %es",
+                "movw {data:x}, %fs",
+                "movw {data:x}, %gs",
+                "movw {data:x}, %ss",
+                code = in(reg) code.bits() as u32,
+                data = in(reg) data.bits() as u32,
                 options(att_syntax)
             );
-            // Use AT&T syntax because ljmp doesn't work with Intel syntax
+            // Use AT&T syntax because far jumps don't work with Intel syntax
             // https://github.com/rust-lang/rust/issues/84676
         }
     }
+}
 
-    pub fn get_gdt_pointer(&self) -> GdtPointer {
-        let gdt_pointer = GdtPointer { limit: 0, base: 0 };
+/// Appends a descriptor to the installed GDT.
+#[allow(dead_code)]
+pub fn add_descriptor(descriptor: SegmentDescriptor) -> Option<SegmentSelector> {
+    let mut gdt = GDT.lock();
+    let selector = gdt.push(descriptor)?;
 
-        unsafe {
-            asm!("sgdt [{x}]", x = in(reg) & gdt_pointer,);
-        }
+    gdt.load();
+    Some(selector)
+}
+
+pub fn load_task_register(selector: SegmentSelector) {
+    unsafe {
+        asm!("ltr {0:x}", in(reg) selector.bits());
+    }
+}
 
-        return gdt_pointer;
+/// Installs the kernel GDT and loads the kernel TSS.
+pub fn init() {
+    let mut builder = GdtBuilder::new();
+
+    let code = builder.add_code_segment(PrivilegeLevel::Ring0);
+    let data = builder.add_data_segment(PrivilegeLevel::Ring0);
+    let stack = DescriptorType::Data {
+        expand_down: true,
+        writable: true,
+    };
+    builder.add_flat_segment(stack, PrivilegeLevel::Ring0);
+    builder.add_code_segment(PrivilegeLevel::Ring3);
+    builder.add_data_segment(PrivilegeLevel::Ring3);
+    builder.add_data_segment(PrivilegeLevel::Ring3);
+
+    let kernel_tss = builder.add_tss(tss::kernel_tss_addr(data.bits(), crate::stack_top as u32));
+    let double_fault_tss = builder.add_tss(tss::double_fault_tss_addr(
+        crate::interrupts::double_fault::double_fault_task as u32,
+        code.bits(),
+        data.bits(),
+    ));
+
+    assert_eq!(code, KERNEL_CODE_SELECTOR);
+    assert_eq!(data, KERNEL_DATA_SELECTOR);
+    assert_eq!(kernel_tss, KERNEL_TSS_SELECTOR);
+    assert_eq!(double_fault_tss, DOUBLE_FAULT_TSS_SELECTOR);
+
+    builder.install(code, data);
+    load_task_register(kernel_tss);
+}
+
+pub fn get_gdt_pointer() -> GdtPointer {
+    let gdt_pointer = GdtPointer { limit: 0, base: 0 };
+
+    unsafe {
+        asm!("sgdt [{x}]", x = in(reg) & gdt_pointer,);
     }
 
-    pub fn print(&self) {
-        let gdt_pointer = self.get_gdt_pointer();
+    return gdt_pointer;
+}
+
+/// Descriptors of the table currently loaded in GDTR.
+pub fn installed_descriptors() -> &'static [SegmentDescriptor] {
+    let gdt_pointer = get_gdt_pointer();
+    let len = (gdt_pointer.limit as usize + 1) / core::mem::size_of::<SegmentDescriptor>();
+
+    unsafe { core::slice::from_raw_parts(gdt_pointer.base as *const SegmentDescriptor, len) }
+}
+
+/// Decodes every descriptor of the installed GDT.
+pub fn print() {
+    let gdt_pointer = get_gdt_pointer();
+    let (base, limit) = (gdt_pointer.base, gdt_pointer.limit);
+
+    println!("GDT at 0x{:08x}, limit {:#x}", base, limit);
+
+    for (index, descriptor) in installed_descriptors().iter().enumerate() {
+        let selector = index * core::mem::size_of::<SegmentDescriptor>();
+        let access = match descriptor.access() {
+            _ if descriptor.is_null() => {
+                println!("{:#06x} null", selector);
+                continue;
+            }
+            Some(access) => access,
+            None => {
+                println!("{:#06x} reserved descriptor type", selector);
+                continue;
+            }
+        };
+        let present = match access.present {
+            true => "present",
+            false => "not-present",
+        };
+
+        if access.kind == DescriptorType::System(SystemType::CallGate) {
+            let (target, offset) = descriptor.gate_target();
+
+            println!(
+                "{:#06x} call gate to {:#06x}:0x{:08x} ({} parameters) DPL{} {}",
+                selector,
+                target.bits(),
+                offset,
+                descriptor.gate_parameters(),
+                access.dpl as u8,
+                present
+            );
+            continue;
+        }
 
-        hexdump(gdt_pointer.base as *const u8, gdt_pointer.limit as usize);
+        println!(
+            "{:#06x} base 0x{:08x} limit 0x{:08x} {:?} DPL{} {} {}",
+            selector,
+            descriptor.base(),
+            descriptor.byte_limit(),
+            descriptor.flags().granularity,
+            access.dpl as u8,
+            present,
+            access.kind
+        );
     }
 }
@@ -0,0 +1,120 @@
+//! 32-bit Task State Segments: the kernel one loaded with `ltr`, and the one the double fault
+//! task gate switches to so that the handler runs on a stack known to be valid.
+//! https://wiki.osdev.org/Task_State_Segment
+
+use crate::utils::asm::read_cr3;
+use core::mem::size_of;
+use core::ptr::addr_of_mut;
+
+const DOUBLE_FAULT_STACK_SIZE: usize = 0x4000;
+
+/// Segment fields are 16-bit wide, the upper half being reserved.
+#[derive(Debug, Clone, Copy)]
+#[repr(C)]
+pub struct TaskStateSegment {
+    pub previous_task_link: u32,
+    pub esp0: u32,
+    pub ss0: u32,
+    pub esp1: u32,
+    pub ss1: u32,
+    pub esp2: u32,
+    pub ss2: u32,
+    pub cr3: u32,
+    pub eip: u32,
+    pub eflags: u32,
+    pub eax: u32,
+    pub ecx: u32,
+    pub edx: u32,
+    pub ebx: u32,
+    pub esp: u32,
+    pub ebp: u32,
+    pub esi: u32,
+    pub edi: u32,
+    pub es: u32,
+    pub cs: u32,
+    pub ss: u32,
+    pub ds: u32,
+    pub fs: u32,
+    pub gs: u32,
+    pub ldt: u32,
+    /// Debug trap flag in bit 0, I/O map base address in the upper half.
+    pub trap_iomap: u32,
+}
+
+impl TaskStateSegment {
+    const fn new() -> Self {
+        TaskStateSegment {
+            previous_task_link: 0,
+            esp0: 0,
+            ss0: 0,
+            esp1: 0,
+            ss1: 0,
+            esp2: 0,
+            ss2: 0,
+            cr3: 0,
+            eip: 0,
+            eflags: 0,
+            eax: 0,
+            ecx: 0,
+            edx: 0,
+            ebx: 0,
+            esp: 0,
+            ebp: 0,
+            esi: 0,
+            edi: 0,
+            es: 0,
+            cs: 0,
+            ss: 0,
+            ds: 0,
+            fs: 0,
+            gs: 0,
+            ldt: 0,
+            // No I/O permission bitmap
+            trap_iomap: (size_of::<TaskStateSegment>() as u32) << 16,
+        }
+    }
+}
+
+#[repr(C, align(16))]
+struct Stack([u8; DOUBLE_FAULT_STACK_SIZE]);
+
+/// Receives the state of the interrupted task on a task switch (e.g. the double fault).
+pub static mut KERNEL_TSS: TaskStateSegment = TaskStateSegment::new();
+static mut DOUBLE_FAULT_TSS: TaskStateSegment = TaskStateSegment::new();
+static mut DOUBLE_FAULT_STACK: Stack = Stack([0; DOUBLE_FAULT_STACK_SIZE]);
+
+/// Prepares the kernel task, `esp0` being the stack used when coming from user mode.
+pub fn kernel_tss_addr(ss0: u16, esp0: u32) -> u32 {
+    unsafe {
+        let tss = &mut *addr_of_mut!(KERNEL_TSS);
+
+        tss.ss0 = ss0 as u32;
+        tss.esp0 = esp0;
+
+        tss as *mut TaskStateSegment as u32
+    }
+}
+
+/// Prepares the double fault task, `entry` runs with interrupts disabled on its own stack.
+pub fn double_fault_tss_addr(entry: u32, code_selector: u16, data_selector: u16) -> u32 {
+    unsafe {
+        let tss = &mut *addr_of_mut!(DOUBLE_FAULT_TSS);
+        let stack = addr_of_mut!(DOUBLE_FAULT_STACK) as u32;
+
+        tss.cr3 = read_cr3();
+        tss.eip = entry;
+        // Only the reserved bit 1 set, interrupts disabled
+        tss.eflags = 0x2;
+        tss.esp = stack + DOUBLE_FAULT_STACK_SIZE as u32;
+        tss.cs = code_selector as u32;
+        tss.ss = data_selector as u32;
+        tss.ds = data_selector as u32;
+        tss.es = data_selector as u32;
+        tss.fs = data_selector as u32;
+        tss.gs = data_selector as u32;
+
+        tss as *mut TaskStateSegment as u32
+    }
+}
+
+pub const TSS_LIMIT: u32 = size_of::<TaskStateSegment>() as u32 - 1;
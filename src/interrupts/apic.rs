@@ -0,0 +1,258 @@
+//! Local APIC and I/O APIC, used instead of the 8259 PICs when the kernel command line holds
+//! `irqchip=apic`. The ISA IRQs keep the vectors they had on the PICs.
+//! https://wiki.osdev.org/APIC
+//! https://wiki.osdev.org/IOAPIC
+
+use crate::acpi::madt::{is_active_low, is_level_triggered, madt, MadtEntry};
+use crate::interrupts::irq::{irq_vector, IRQ_COUNT};
+use crate::interrupts::TrapFrame;
+use crate::memory::paging::{identity_map, MapError, PageFlags};
+use crate::utils::asm::{cpuid, read_msr, write_msr};
+use core::ptr::{read_volatile, write_volatile};
+use spin::{Mutex, Once};
+
+const CPUID_FEATURES: u32 = 1;
+const CPUID_EDX_APIC: u32 = 1 << 9;
+
+const IA32_APIC_BASE_MSR: u32 = 0x1B;
+const APIC_BASE_ENABLE: u64 = 1 << 11;
+
+// Local APIC registers
+const LAPIC_ID: u32 = 0x20;
+const LAPIC_EOI: u32 = 0xB0;
+const LAPIC_SPURIOUS: u32 = 0xF0;
+const LAPIC_SOFTWARE_ENABLE: u32 = 1 << 8;
+const LAPIC_SIZE: u32 = 0x400;
+
+// I/O APIC registers, accessed through a select register and a window
+const IOAPIC_SELECT: u32 = 0x00;
+const IOAPIC_WINDOW: u32 = 0x10;
+const IOAPIC_VERSION: u32 = 0x01;
+const IOAPIC_REDIRECTION_TABLE: u32 = 0x10;
+const IOAPIC_SIZE: u32 = 0x20;
+
+const REDIRECTION_MASKED: u32 = 1 << 16;
+const REDIRECTION_LEVEL_TRIGGERED: u32 = 1 << 15;
+const REDIRECTION_ACTIVE_LOW: u32 = 1 << 13;
+
+/// Raised by the local APIC when an interrupt vanished before being delivered, gets no EOI.
+pub const SPURIOUS_VECTOR: u8 = 0xFF;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ApicError {
+    NotSupported,
+    NoMadt,
+    NoIoApic,
+    MapFailed(MapError),
+}
+
+struct LocalApic {
+    base: u32,
+}
+
+impl LocalApic {
+    fn read(&self, register: u32) -> u32 {
+        unsafe { read_volatile((self.base + register) as *const u32) }
+    }
+
+    fn write(&self, register: u32, value: u32) {
+        unsafe { write_volatile((self.base + register) as *mut u32, value) }
+    }
+
+    fn id(&self) -> u8 {
+        (self.read(LAPIC_ID) >> 24) as u8
+    }
+}
+
+struct IoApic {
+    base: u32,
+    gsi_base: u32,
+    gsi_count: u32,
+}
+
+impl IoApic {
+    fn read(&mut self, register: u32) -> u32 {
+        unsafe {
+            write_volatile((self.base + IOAPIC_SELECT) as *mut u32, register);
+            read_volatile((self.base + IOAPIC_WINDOW) as *const u32)
+        }
+    }
+
+    fn write(&mut self, register: u32, value: u32) {
+        unsafe {
+            write_volatile((self.base + IOAPIC_SELECT) as *mut u32, register);
+            write_volatile((self.base + IOAPIC_WINDOW) as *mut u32, value);
+        }
+    }
+
+    fn redirection_entries(&mut self) -> u32 {
+        ((self.read(IOAPIC_VERSION) >> 16) & 0xFF) + 1
+    }
+
+    fn handles(&self, gsi: u32) -> bool {
+        gsi >= self.gsi_base && gsi - self.gsi_base < self.gsi_count
+    }
+
+    /// Redirection entry of `gsi`, as its low and high dwords.
+    fn set_redirection(&mut self, gsi: u32, low: u32, high: u32) {
+        let register = IOAPIC_REDIRECTION_TABLE + (gsi - self.gsi_base) * 2;
+
+        // Write the mask bit last, the entry must be complete when it gets unmasked
+        self.write(register + 1, high);
+        self.write(register, low);
+    }
+
+    fn set_masked(&mut self, gsi: u32, masked: bool) {
+        let register = IOAPIC_REDIRECTION_TABLE + (gsi - self.gsi_base) * 2;
+        let low = self.read(register);
+
+        match masked {
+            true => self.write(register, low | REDIRECTION_MASKED),
+            false => self.write(register, low & !REDIRECTION_MASKED),
+        }
+    }
+}
+
+pub struct Apic {
+    local: LocalApic,
+    io: Mutex<IoApic>,
+    /// Global System Interrupt each ISA IRQ is wired to.
+    irq_gsi: [Option<u32>; IRQ_COUNT as usize],
+}
+
+static APIC: Once<Apic> = Once::new();
+
+pub fn is_supported() -> bool {
+    cpuid(CPUID_FEATURES).edx & CPUID_EDX_APIC != 0
+}
+
+fn map_registers(base: u32, size: u32) -> Result<(), ApicError> {
+    let flags = PageFlags::PRESENT | PageFlags::WRITABLE | PageFlags::CACHE_DISABLE;
+
+    identity_map(base, base + size, flags).map_err(ApicError::MapFailed)
+}
+
+/// Enables the local APIC and routes the ISA IRQs through the first I/O APIC, all masked.
+///
+/// The 8259 PICs must have been remapped and masked, they can still raise spurious interrupts.
+pub fn init() -> Result<(), ApicError> {
+    if !is_supported() {
+        return Err(ApicError::NotSupported);
+    }
+
+    let madt = madt().ok_or(ApicError::NoMadt)?;
+    let (io_base, gsi_base) = madt
+        .entries()
+        .find_map(|entry| match entry {
+            MadtEntry::IoApic {
+                address, gsi_base, ..
+            } => Some((address, gsi_base)),
+            _ => None,
+        })
+        .ok_or(ApicError::NoIoApic)?;
+
+    map_registers(madt.local_apic_address, LAPIC_SIZE)?;
+    map_registers(io_base, IOAPIC_SIZE)?;
+
+    let local = LocalApic {
+        base: madt.local_apic_address,
+    };
+    let mut io = IoApic {
+        base: io_base,
+        gsi_base,
+        gsi_count: 0,
+    };
+
+    unsafe {
+        let apic_base = read_msr(IA32_APIC_BASE_MSR);
+        write_msr(IA32_APIC_BASE_MSR, apic_base | APIC_BASE_ENABLE);
+    }
+    local.write(
+        LAPIC_SPURIOUS,
+        LAPIC_SOFTWARE_ENABLE | SPURIOUS_VECTOR as u32,
+    );
+
+    io.gsi_count = io.redirection_entries();
+    for gsi in gsi_base..gsi_base + io.gsi_count {
+        io.set_redirection(gsi, REDIRECTION_MASKED, 0);
+    }
+
+    // ISA IRQs are edge triggered, active high and identity mapped unless overridden
+    let mut irq_gsi = [None; IRQ_COUNT as usize];
+    let mut irq_flags = [0; IRQ_COUNT as usize];
+    for entry in madt.entries() {
+        if let MadtEntry::InterruptOverride {
+            bus: 0,
+            source,
+            gsi,
+            flags,
+        } = entry
+        {
+            if source < IRQ_COUNT {
+                irq_gsi[usize::from(source)] = Some(gsi);
+                irq_flags[usize::from(source)] = flags;
+            }
+        }
+    }
+    for irq in 0..IRQ_COUNT {
+        let gsi = irq as u32;
+        // The timer is usually moved to GSI 2, leaving IRQ 2 without a line
+        let taken = irq_gsi.contains(&Some(gsi));
+
+        if irq_gsi[usize::from(irq)].is_none() && !taken {
+            irq_gsi[usize::from(irq)] = Some(gsi);
+        }
+    }
+
+    let destination = (local.id() as u32) << 24;
+    for irq in 0..IRQ_COUNT {
+        let flags = irq_flags[usize::from(irq)];
+        let mut low = REDIRECTION_MASKED | irq_vector(irq) as u32;
+
+        let gsi = match irq_gsi[usize::from(irq)] {
+            Some(gsi) if io.handles(gsi) => gsi,
+            _ => continue,
+        };
+        if is_active_low(flags) {
+            low |= REDIRECTION_ACTIVE_LOW;
+        }
+        if is_level_triggered(flags) {
+            low |= REDIRECTION_LEVEL_TRIGGERED;
+        }
+        io.set_redirection(gsi, low, destination);
+    }
+
+    APIC.call_once(|| Apic {
+        local,
+        io: Mutex::new(io),
+        irq_gsi,
+    });
+    Ok(())
+}
+
+fn set_irq_masked(irq: u8, masked: bool) {
+    if let Some(apic) = APIC.r#try() {
+        let mut io = apic.io.lock();
+
+        match apic.irq_gsi[usize::from(irq)] {
+            Some(gsi) if io.handles(gsi) => io.set_masked(gsi, masked),
+            _ => {}
+        }
+    }
+}
+
+pub fn mask_irq(irq: u8) {
+    set_irq_masked(irq, true);
+}
+
+pub fn unmask_irq(irq: u8) {
+    set_irq_masked(irq, false);
+}
+
+pub fn end_of_interrupt() {
+    if let Some(apic) = APIC.r#try() {
+        apic.local.write(LAPIC_EOI, 0);
+    }
+}
+
+pub fn spurious_isr(_: &mut TrapFrame) {}
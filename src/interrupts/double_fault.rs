@@ -0,0 +1,61 @@
+//! Double fault handler, reached through a task gate so that it gets a fresh stack even when
+//! the fault comes from a kernel stack overflow.
+
+use crate::gdt::tss::KERNEL_TSS;
+use crate::serial::SERIAL1;
+use crate::vga_buffer::WRITER;
+use crate::{println, stack_bottom, stack_guard};
+use core::arch::global_asm;
+use core::ptr::addr_of;
+
+global_asm!(
+    ".global double_fault_task",
+    "double_fault_task:",
+    // The CPU pushed the error code on the new stack, it becomes the handler argument
+    "    call double_fault_handler",
+);
+
+extern "C" {
+    pub fn double_fault_task();
+}
+
+#[no_mangle]
+extern "C" fn double_fault_handler(error_code: u32) -> ! {
+    // The interrupted task may have been printing, and it will never run again
+    unsafe {
+        WRITER.force_unlock();
+        SERIAL1.force_unlock();
+    }
+
+    // The task switch saved the state of the interrupted code in the kernel TSS
+    let tss = unsafe { &*addr_of!(KERNEL_TSS) };
+
+    println!("DoubleFault exception occured");
+    println!("Error code {:#010x}", error_code);
+    println!(
+        "EAX={:08x} EBX={:08x} ECX={:08x} EDX={:08x}",
+        tss.eax, tss.ebx, tss.ecx, tss.edx
+    );
+    println!(
+        "ESI={:08x} EDI={:08x} EBP={:08x} ESP={:08x}",
+        tss.esi, tss.edi, tss.ebp, tss.esp
+    );
+    println!(
+        "EIP={:08x} EFLAGS={:08x} CR3={:08x}",
+        tss.eip, tss.eflags, tss.cr3
+    );
+    println!(
+        "CS={:04x} DS={:04x} ES={:04x} FS={:04x} GS={:04x} SS={:04x}",
+        tss.cs & 0xFFFF,
+        tss.ds & 0xFFFF,
+        tss.es & 0xFFFF,
+        tss.fs & 0xFFFF,
+        tss.gs & 0xFFFF,
+        tss.ss & 0xFFFF
+    );
+
+    if (stack_guard as u32..stack_bottom as u32 + 0x40).contains(&tss.esp) {
+        panic!("Kernel stack overflow");
+    }
+    panic!("Unrecoverable DoubleFault exception");
+}
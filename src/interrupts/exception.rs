@@ -0,0 +1,183 @@
+//! Exception policy: traps are reported and resumed, faults abort the code that registered a
+//! recovery point with `catch_faults` (the CLI commands), anything else is fatal. A fault raised
+//! by an interrupt handler that nested inside `catch_faults` is fatal too: aborting it would
+//! leave its interrupt unacknowledged.
+
+use crate::interrupts::trap::trap_depth;
+use crate::interrupts::{are_interrupts_enabled, InterruptIndex, TrapFrame};
+use crate::println;
+use core::arch::global_asm;
+use core::sync::atomic::{AtomicPtr, Ordering};
+
+/// Bit 1 of EFLAGS, always set.
+const EFLAGS_RESERVED: u32 = 1 << 1;
+const EFLAGS_INTERRUPT_ENABLE: u32 = 1 << 9;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExceptionClass {
+    /// Reported, then execution resumes after the faulting instruction.
+    Trap,
+    /// Recoverable if the faulting code can be aborted.
+    Fault,
+    /// The machine state can't be trusted anymore.
+    Abort,
+}
+
+impl InterruptIndex {
+    pub fn exception_class(self) -> ExceptionClass {
+        match self {
+            InterruptIndex::Debugger
+            | InterruptIndex::NMI
+            | InterruptIndex::Breakpoint
+            | InterruptIndex::Overflow => ExceptionClass::Trap,
+            InterruptIndex::DoubleFault | InterruptIndex::MachineCheck => ExceptionClass::Abort,
+            _ => ExceptionClass::Fault,
+        }
+    }
+}
+
+/// Describes the fault that aborted a `catch_faults` call.
+#[derive(Debug, Clone, Copy)]
+pub struct FaultReport {
+    pub exception: InterruptIndex,
+    pub error_code: u32,
+    pub instruction_pointer: u32,
+}
+
+/// Callee-saved registers of `recovery_call`, restored by `recovery_resume`.
+#[derive(Default)]
+#[repr(C)]
+struct RecoveryPoint {
+    ebx: u32,
+    esi: u32,
+    edi: u32,
+    ebp: u32,
+    esp: u32,
+    /// Interrupt nesting depth of the code that called `catch_faults`, only its faults abort it.
+    trap_depth: usize,
+    /// Whether interrupts were enabled in `catch_faults`, restored when resuming there.
+    interrupts_enabled: bool,
+    report: Option<FaultReport>,
+}
+
+global_asm!(
+    // u32 recovery_call(RecoveryPoint *point, void (*func)(void *), void *arg)
+    // Returns 0 once `func` returned, or 1 when `recovery_resume` aborted it.
+    ".global recovery_call",
+    ".global recovery_resume",
+    "recovery_call:",
+    "    mov eax, [esp + 4]",
+    "    mov [eax], ebx",
+    "    mov [eax + 4], esi",
+    "    mov [eax + 8], edi",
+    "    mov [eax + 12], ebp",
+    "    mov [eax + 16], esp",
+    "    push dword ptr [esp + 12]",
+    "    call dword ptr [esp + 12]",
+    "    add esp, 4",
+    "    xor eax, eax",
+    "    ret",
+    "",
+    // Entered through `iretd` with EAX pointing to the RecoveryPoint
+    "recovery_resume:",
+    "    mov ebx, [eax]",
+    "    mov esi, [eax + 4]",
+    "    mov edi, [eax + 8]",
+    "    mov ebp, [eax + 12]",
+    "    mov esp, [eax + 16]",
+    "    mov eax, 1",
+    "    ret",
+);
+
+extern "C" {
+    fn recovery_call(point: usize, func: usize, arg: usize) -> u32;
+    fn recovery_resume();
+}
+
+/// Innermost recovery point, faults are delivered to it.
+static RECOVERY_POINT: AtomicPtr<RecoveryPoint> = AtomicPtr::new(core::ptr::null_mut());
+
+extern "C" fn call_once<F: FnOnce()>(func: *mut Option<F>) {
+    if let Some(func) = unsafe { (*func).take() } {
+        func();
+    }
+}
+
+/// Runs `func`, aborting it if it raises a fault instead of halting the kernel.
+///
+/// Nothing is dropped when `func` is aborted and locks it held stay locked.
+pub fn catch_faults<F: FnOnce()>(func: F) -> Result<(), FaultReport> {
+    let mut point = RecoveryPoint {
+        trap_depth: trap_depth(),
+        interrupts_enabled: are_interrupts_enabled(),
+        ..Default::default()
+    };
+    let mut func = Some(func);
+    let previous = RECOVERY_POINT.swap(&mut point, Ordering::SeqCst);
+
+    let aborted = unsafe {
+        recovery_call(
+            &mut point as *mut RecoveryPoint as usize,
+            call_once::<F> as usize,
+            &mut func as *mut Option<F> as usize,
+        )
+    };
+
+    RECOVERY_POINT.store(previous, Ordering::SeqCst);
+
+    match (aborted, point.report) {
+        (0, _) => Ok(()),
+        (_, Some(report)) => Err(report),
+        (_, None) => unreachable!(),
+    }
+}
+
+/// Whether the fault being handled was raised by the code running the innermost `catch_faults`
+/// call, rather than by an interrupt handler nested in it.
+fn can_recover() -> bool {
+    let point = RECOVERY_POINT.load(Ordering::SeqCst);
+
+    // The handler of the fault itself is one level deeper than the faulting code
+    !point.is_null() && unsafe { (*point).trap_depth } + 1 == trap_depth()
+}
+
+/// Makes the interrupted context resume in the innermost `catch_faults` call.
+fn recover(exception: InterruptIndex, frame: &mut TrapFrame) {
+    let point = RECOVERY_POINT.load(Ordering::SeqCst);
+
+    unsafe {
+        (*point).report = Some(FaultReport {
+            exception,
+            error_code: frame.error_code,
+            instruction_pointer: frame.eip,
+        });
+        // The aborted code may have faulted with the interrupts disabled or the direction flag
+        // set, only the interrupt flag `catch_faults` was called with is kept
+        frame.eflags = match (*point).interrupts_enabled {
+            true => EFLAGS_RESERVED | EFLAGS_INTERRUPT_ENABLE,
+            false => EFLAGS_RESERVED,
+        };
+    }
+    frame.eip = recovery_resume as u32;
+    frame.eax = point as u32;
+}
+
+pub fn handle_exception(exception: InterruptIndex, frame: &mut TrapFrame) {
+    let class = exception.exception_class();
+    let recoverable = match class {
+        ExceptionClass::Trap => true,
+        ExceptionClass::Fault => can_recover(),
+        ExceptionClass::Abort => false,
+    };
+
+    if !recoverable {
+        panic!("Unrecoverable {:?} exception", exception);
+    }
+
+    println!("{:?} exception occured", exception);
+    println!("{}", frame);
+
+    if class == ExceptionClass::Fault {
+        recover(exception, frame);
+    }
+}
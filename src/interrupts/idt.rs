@@ -1,15 +1,8 @@
 //! Provides types for the Interrupt Descriptor Table and its entries.
 
-/// Represents the interrupt stack frame pushed by the CPU on interrupt or exception entry.
-#[derive(Clone, Copy, Debug)]
-#[repr(C)]
-pub struct InterruptStackFrame {
-    pub instruction_pointer: u32,
-    pub code_segment: u32,
-    pub cpu_flags: u32,
-    pub stack_pointer: u32,
-    pub stack_segment: u32,
-}
+use crate::gdt::descriptor::PrivilegeLevel;
+use crate::interrupts::InterruptIndex;
+use crate::{print, println};
 
 #[repr(C, packed)]
 #[derive(Copy, Clone)]
@@ -31,13 +24,59 @@ impl InterruptDescriptor {
             isr_high: ((offset >> 16) & 0xFFFF) as u16,
         }
     }
+
+    pub fn offset(&self) -> u32 {
+        self.isr_low as u32 | (self.isr_high as u32) << 16
+    }
+
+    pub fn selector(&self) -> u16 {
+        self.kernel_cs
+    }
+
+    pub fn present(&self) -> bool {
+        self.type_attributes & (1 << 7) != 0
+    }
+
+    pub fn dpl(&self) -> PrivilegeLevel {
+        PrivilegeLevel::from_bits(self.type_attributes >> 5)
+    }
+
+    pub fn gate_type(&self) -> Option<GateType> {
+        GateType::from_bits(self.type_attributes)
+    }
+
+    fn is_unset(&self) -> bool {
+        self.type_attributes == 0 && self.offset() == 0 && self.kernel_cs == 0
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GateType {
+    Task,
+    Interrupt16,
+    Trap16,
+    /// Interrupts are disabled on entry.
+    Interrupt32,
+    Trap32,
+}
+
+impl GateType {
+    fn from_bits(bits: u8) -> Option<Self> {
+        match bits & 0xF {
+            0x5 => Some(GateType::Task),
+            0x6 => Some(GateType::Interrupt16),
+            0x7 => Some(GateType::Trap16),
+            0xE => Some(GateType::Interrupt32),
+            0xF => Some(GateType::Trap32),
+            _ => None,
+        }
+    }
 }
 
 // Structure représentant la table des descripteurs d'interruption
 #[repr(C, align(0x10))]
 pub struct InterruptDescriptorTable {
     pub descriptors: [InterruptDescriptor; 256],
-    pub ptr: IDTR,
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -51,7 +90,6 @@ impl InterruptDescriptorTable {
     pub fn new() -> Self {
         Self {
             descriptors: [InterruptDescriptor::new(0, 0, 0); 256],
-            ptr: IDTR { limit: 0, base: 0 },
         }
     }
 
@@ -60,11 +98,89 @@ impl InterruptDescriptorTable {
     }
 
     pub fn load(&'static self) {
+        let ptr = IDTR {
+            base: self.descriptors.as_ptr() as u32,
+            limit: (core::mem::size_of::<InterruptDescriptor>() * 256 - 1) as u16,
+        };
+
         unsafe {
             core::arch::asm!(
                 "lidt [{}]",
-                in(reg) &self.ptr as *const _ as u32
+                in(reg) &ptr as *const _ as u32
             );
         }
     }
 }
+
+pub fn get_idtr() -> IDTR {
+    let idtr = IDTR { limit: 0, base: 0 };
+
+    unsafe {
+        core::arch::asm!("sidt [{}]", in(reg) &idtr);
+    }
+
+    idtr
+}
+
+/// Descriptors of the table currently loaded in IDTR.
+pub fn installed_descriptors() -> &'static [InterruptDescriptor] {
+    let idtr = get_idtr();
+    let len = (idtr.limit as usize + 1) / core::mem::size_of::<InterruptDescriptor>();
+
+    unsafe { core::slice::from_raw_parts(idtr.base as *const InterruptDescriptor, len) }
+}
+
+/// Decodes the 256 vectors of the installed IDT, runs of unset vectors on a single line.
+pub fn print() {
+    let idtr = get_idtr();
+    let (base, limit) = (idtr.base, idtr.limit);
+    let descriptors = installed_descriptors();
+    let mut vector = 0;
+
+    println!("IDT at 0x{:08x}, limit {:#x}", base, limit);
+
+    while vector < 256 {
+        let descriptor = match descriptors.get(vector) {
+            Some(descriptor) if !descriptor.is_unset() => descriptor,
+            // Past the limit, the vector raises a general protection fault
+            _ => {
+                let first = vector;
+
+                while vector < 256 && !descriptors.get(vector).is_some_and(|d| !d.is_unset()) {
+                    vector += 1;
+                }
+                match vector - first {
+                    1 => println!("{:#04x} unset", first),
+                    _ => println!("{:#04x}-{:#04x} unset", first, vector - 1),
+                }
+                continue;
+            }
+        };
+        let present = match descriptor.present() {
+            true => "present",
+            false => "not-present",
+        };
+
+        match descriptor.gate_type() {
+            Some(GateType::Task) => print!(
+                "{:#04x} task gate to TSS {:#06x}",
+                vector,
+                descriptor.selector()
+            ),
+            Some(gate_type) => print!(
+                "{:#04x} 0x{:08x} selector {:#06x} {:?}",
+                vector,
+                descriptor.offset(),
+                descriptor.selector(),
+                gate_type
+            ),
+            None => print!("{:#04x} reserved gate type", vector),
+        }
+        print!(" DPL{} {}", descriptor.dpl() as u8, present);
+        match InterruptIndex::from_vector(vector as u8) {
+            Some(index) => println!(" {:?}", index),
+            None => println!(),
+        }
+        vector += 1;
+    }
+}
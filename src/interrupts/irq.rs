@@ -0,0 +1,152 @@
+//! ISA interrupt lines, delivered by the chained PICs or by the APIC.
+//!
+//! Drivers register a handler for an IRQ line, which stays unmasked as long as the handler is
+//! registered. Every IRQ vector goes through `irq_dispatch`, which filters out spurious
+//! interrupts and sends the end of interrupt once the handler returned.
+
+use crate::interrupts::pic8259::{CASCADE_IRQ, PICS, PIC_1_OFFSET};
+use crate::interrupts::{apic, without_interrupts, TrapFrame};
+use crate::println;
+use spin::{Mutex, Once};
+
+pub const IRQ_COUNT: u8 = 16;
+
+pub const TIMER_IRQ: u8 = 0;
+pub const KEYBOARD_IRQ: u8 = 1;
+
+pub type IrqHandler = fn(&mut TrapFrame);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IrqError {
+    InvalidLine,
+    AlreadyRegistered,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InterruptController {
+    Pic8259,
+    Apic,
+}
+
+static CONTROLLER: Once<InterruptController> = Once::new();
+
+static IRQ_HANDLERS: Mutex<[Option<IrqHandler>; IRQ_COUNT as usize]> =
+    Mutex::new([None; IRQ_COUNT as usize]);
+
+/// Vector raised for `irq`, the same with both controllers.
+pub fn irq_vector(irq: u8) -> u8 {
+    PIC_1_OFFSET + irq
+}
+
+pub fn controller() -> InterruptController {
+    CONTROLLER
+        .r#try()
+        .copied()
+        .unwrap_or(InterruptController::Pic8259)
+}
+
+/// Sets up the requested controller with every line masked, lines get unmasked as handlers are
+/// registered. Falls back to the PICs when the APIC can't be used.
+pub fn init(requested: InterruptController) -> InterruptController {
+    without_interrupts(|| {
+        let mut pics = PICS.lock();
+
+        // Remapped even when unused, so that their spurious interrupts don't look like exceptions
+        pics.initialize();
+        pics.write_masks(!(1 << CASCADE_IRQ));
+
+        if requested == InterruptController::Apic {
+            pics.disable();
+            drop(pics);
+
+            match apic::init() {
+                Ok(()) => return *CONTROLLER.call_once(|| InterruptController::Apic),
+                Err(error) => {
+                    println!("APIC unavailable ({:?}), using the 8259 PICs", error);
+                    PICS.lock().write_masks(!(1 << CASCADE_IRQ));
+                }
+            }
+        }
+        *CONTROLLER.call_once(|| InterruptController::Pic8259)
+    })
+}
+
+fn set_masked(irq: u8, masked: bool) {
+    match (controller(), masked) {
+        (InterruptController::Pic8259, true) => PICS.lock().mask_irq(irq),
+        (InterruptController::Pic8259, false) => PICS.lock().unmask_irq(irq),
+        (InterruptController::Apic, true) => apic::mask_irq(irq),
+        (InterruptController::Apic, false) => apic::unmask_irq(irq),
+    }
+}
+
+pub fn register_handler(irq: u8, handler: IrqHandler) -> Result<(), IrqError> {
+    if irq >= IRQ_COUNT {
+        return Err(IrqError::InvalidLine);
+    }
+
+    without_interrupts(|| {
+        let mut handlers = IRQ_HANDLERS.lock();
+        let slot = &mut handlers[usize::from(irq)];
+
+        if slot.is_some() {
+            return Err(IrqError::AlreadyRegistered);
+        }
+        *slot = Some(handler);
+        set_masked(irq, false);
+        Ok(())
+    })
+}
+
+/// Masks `irq` and removes its handler.
+#[allow(dead_code)]
+pub fn unregister_handler(irq: u8) {
+    if irq >= IRQ_COUNT {
+        return;
+    }
+
+    without_interrupts(|| {
+        IRQ_HANDLERS.lock()[usize::from(irq)] = None;
+        if irq != CASCADE_IRQ {
+            set_masked(irq, true);
+        }
+    });
+}
+
+#[allow(dead_code)]
+pub fn mask_irq(irq: u8) {
+    if irq < IRQ_COUNT {
+        without_interrupts(|| set_masked(irq, true));
+    }
+}
+
+#[allow(dead_code)]
+pub fn unmask_irq(irq: u8) {
+    if irq < IRQ_COUNT {
+        without_interrupts(|| set_masked(irq, false));
+    }
+}
+
+/// Trap handler of the vectors `irq_vector(0)` to `irq_vector(IRQ_COUNT - 1)`.
+pub fn irq_dispatch(frame: &mut TrapFrame) {
+    let controller = controller();
+
+    if controller == InterruptController::Pic8259
+        && PICS.lock().handle_spurious_interrupt(frame.vector as u8)
+    {
+        return;
+    }
+
+    let irq = frame.vector as u8 - PIC_1_OFFSET;
+    // Copy the handler so that it can itself (un)register handlers
+    let handler = IRQ_HANDLERS.lock()[usize::from(irq)];
+
+    if let Some(handler) = handler {
+        handler(frame);
+    }
+
+    match controller {
+        InterruptController::Pic8259 => PICS.lock().notify_end_of_interrupt(frame.vector as u8),
+        InterruptController::Apic => apic::end_of_interrupt(),
+    }
+}
@@ -1,12 +1,25 @@
-mod idt;
+pub mod apic;
+pub mod double_fault;
+pub mod exception;
+pub mod idt;
+pub mod irq;
 pub mod isr;
+pub mod page_fault;
 pub mod pic8259;
+pub mod trap;
 
+use crate::gdt::{DOUBLE_FAULT_TSS_SELECTOR, KERNEL_CODE_SELECTOR};
+use crate::println;
 use core::arch::asm;
-use idt::{InterruptDescriptor, InterruptDescriptorTable, InterruptStackFrame};
+use core::sync::atomic::{AtomicU32, Ordering};
+use idt::{InterruptDescriptor, InterruptDescriptorTable};
 use isr::*;
 use lazy_static::lazy_static;
+use page_fault::page_fault_isr;
 use pic8259::PIC_1_OFFSET;
+pub use trap::TrapFrame;
+
+type TrapHandler = fn(&mut TrapFrame);
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 #[repr(u8)]
@@ -43,6 +56,35 @@ pub enum InterruptIndex {
 }
 
 impl InterruptIndex {
+    const ALL: [InterruptIndex; 26] = [
+        InterruptIndex::DivisionByZero,
+        InterruptIndex::Debugger,
+        InterruptIndex::NMI,
+        InterruptIndex::Breakpoint,
+        InterruptIndex::Overflow,
+        InterruptIndex::Bounds,
+        InterruptIndex::InvalidOpcode,
+        InterruptIndex::CoprocessorNotAvailable,
+        InterruptIndex::DoubleFault,
+        InterruptIndex::CoprocessorSegmentOverrun,
+        InterruptIndex::InvalidTaskStateSegment,
+        InterruptIndex::SegmentNotPresent,
+        InterruptIndex::StackFault,
+        InterruptIndex::GeneralProtectionFault,
+        InterruptIndex::PageFault,
+        InterruptIndex::MathFault,
+        InterruptIndex::AlignmentCheck,
+        InterruptIndex::MachineCheck,
+        InterruptIndex::SIMDException,
+        InterruptIndex::VirtualizationException,
+        InterruptIndex::ControlProtectionException,
+        InterruptIndex::HypervisorInjectionException,
+        InterruptIndex::VMMException,
+        InterruptIndex::SecurityException,
+        InterruptIndex::Timer,
+        InterruptIndex::Keyboard,
+    ];
+
     fn as_u8(self) -> u8 {
         self as u8
     }
@@ -50,6 +92,13 @@ impl InterruptIndex {
     fn as_usize(self) -> usize {
         usize::from(self.as_u8())
     }
+
+    pub fn from_vector(vector: u8) -> Option<Self> {
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|index| index.as_u8() == vector)
+    }
 }
 
 pub fn init_idt() {
@@ -57,15 +106,12 @@ pub fn init_idt() {
 }
 
 lazy_static! {
-    static ref IDT: InterruptDescriptorTable = {
-        let mut idt = InterruptDescriptorTable::new();
+    static ref TRAP_HANDLERS: [Option<TrapHandler>; 256] = {
+        let mut handlers: [Option<TrapHandler>; 256] = [None; 256];
 
         macro_rules! set_isr {
             ($handler_name:ident, $enum_value:expr) => {
-                idt.set_descriptor(
-                    $enum_value.as_usize(),
-                    InterruptDescriptor::new($handler_name as u32, 0x08, 0x8E),
-                );
+                handlers[$enum_value.as_usize()] = Some($handler_name);
             };
         }
 
@@ -80,7 +126,6 @@ lazy_static! {
             coprocessor_not_available_isr,
             InterruptIndex::CoprocessorNotAvailable
         );
-        set_isr!(double_fault_isr, InterruptIndex::DoubleFault);
         set_isr!(
             coprocessor_segment_overrun_isr,
             InterruptIndex::CoprocessorSegmentOverrun
@@ -115,18 +160,57 @@ lazy_static! {
         set_isr!(vmm_exception_isr, InterruptIndex::VMMException);
         set_isr!(security_exception_isr, InterruptIndex::SecurityException);
 
-        set_isr!(timer_isr, InterruptIndex::Timer);
-        set_isr!(keyboard_interrupt_handler, InterruptIndex::Keyboard);
+        for irq in 0..irq::IRQ_COUNT {
+            handlers[usize::from(irq::irq_vector(irq))] = Some(irq::irq_dispatch);
+        }
+        handlers[usize::from(apic::SPURIOUS_VECTOR)] = Some(apic::spurious_isr);
+
+        handlers
+    };
+
+    static ref IDT: InterruptDescriptorTable = {
+        let mut idt = InterruptDescriptorTable::new();
+
+        for (vector, handler) in TRAP_HANDLERS.iter().enumerate() {
+            if handler.is_some() {
+                idt.set_descriptor(
+                    vector,
+                    InterruptDescriptor::new(
+                        trap::trap_stub(vector),
+                        KERNEL_CODE_SELECTOR.bits(),
+                        0x8E,
+                    ),
+                );
+            }
+        }
 
-        idt.ptr = idt::IDTR {
-            base: &idt as *const _ as u32,
-            limit: (core::mem::size_of::<InterruptDescriptor>() * 256 - 1) as u16,
-        };
+        // Task gate, the double fault handler gets its own TSS and stack
+        idt.set_descriptor(
+            InterruptIndex::DoubleFault.as_usize(),
+            InterruptDescriptor::new(0, DOUBLE_FAULT_TSS_SELECTOR.bits(), 0x85),
+        );
 
         idt
     };
 }
 
+/// Number of times each vector was dispatched, spurious interrupts included.
+static INTERRUPT_COUNTS: [AtomicU32; 256] = [const { AtomicU32::new(0) }; 256];
+
+pub fn interrupt_count(vector: u8) -> u32 {
+    INTERRUPT_COUNTS[usize::from(vector)].load(Ordering::Relaxed)
+}
+
+/// Called by `trap_dispatch` with the saved context of every interrupt.
+fn dispatch(frame: &mut TrapFrame) {
+    INTERRUPT_COUNTS[frame.vector as usize].fetch_add(1, Ordering::Relaxed);
+
+    match TRAP_HANDLERS[frame.vector as usize] {
+        Some(handler) => handler(frame),
+        None => println!("Unhandled interrupt vector {:#x}", frame.vector),
+    }
+}
+
 /// Returns whether interrupts are enabled.
 pub fn are_interrupts_enabled() -> bool {
     let eflags: u32;
@@ -0,0 +1,159 @@
+//! Page fault decoding and resolution.
+//! https://wiki.osdev.org/Exceptions#Page_Fault
+
+use crate::interrupts::exception::handle_exception;
+use crate::interrupts::{InterruptIndex, TrapFrame};
+use crate::memory::paging::{PageFlags, PAGING};
+use crate::println;
+use crate::utils::asm::read_cr2;
+use spin::Mutex;
+
+const MAX_RESOLVERS: usize = 8;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PageFaultErrorCode(u32);
+
+impl PageFaultErrorCode {
+    /// The fault was a protection violation rather than a non-present page.
+    pub fn present(self) -> bool {
+        self.0 & (1 << 0) != 0
+    }
+
+    pub fn write(self) -> bool {
+        self.0 & (1 << 1) != 0
+    }
+
+    pub fn user(self) -> bool {
+        self.0 & (1 << 2) != 0
+    }
+
+    /// A reserved bit was set in one of the paging structures.
+    pub fn reserved_bit(self) -> bool {
+        self.0 & (1 << 3) != 0
+    }
+
+    pub fn instruction_fetch(self) -> bool {
+        self.0 & (1 << 4) != 0
+    }
+}
+
+impl core::fmt::Display for PageFaultErrorCode {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        let cause = match self.present() {
+            true => "protection violation",
+            false => "non-present page",
+        };
+        let access = match (self.instruction_fetch(), self.write()) {
+            (true, _) => "instruction fetch",
+            (false, true) => "write",
+            (false, false) => "read",
+        };
+        let mode = match self.user() {
+            true => "user",
+            false => "kernel",
+        };
+
+        write!(f, "{} on {} from {} mode", cause, access, mode)?;
+        if self.reserved_bit() {
+            write!(f, ", reserved bit set")?;
+        }
+        Ok(())
+    }
+}
+
+#[allow(dead_code)]
+#[derive(Debug, Clone, Copy)]
+pub struct PageFaultInfo {
+    /// Linear address that caused the fault, read from CR2.
+    pub address: u32,
+    pub error_code: PageFaultErrorCode,
+    pub instruction_pointer: u32,
+}
+
+/// Returns `true` when the fault has been handled and the instruction can be retried.
+pub type PageFaultResolver = fn(&PageFaultInfo) -> bool;
+
+static RESOLVERS: Mutex<[Option<PageFaultResolver>; MAX_RESOLVERS]> =
+    Mutex::new([None; MAX_RESOLVERS]);
+
+/// Registers a resolver consulted, in registration order, before a page fault is fatal.
+#[allow(dead_code)]
+pub fn register_resolver(resolver: PageFaultResolver) -> bool {
+    let mut resolvers = RESOLVERS.lock();
+
+    match resolvers.iter_mut().find(|slot| slot.is_none()) {
+        Some(slot) => {
+            *slot = Some(resolver);
+            true
+        }
+        None => false,
+    }
+}
+
+#[allow(dead_code)]
+pub fn unregister_resolver(resolver: PageFaultResolver) {
+    for slot in RESOLVERS.lock().iter_mut() {
+        if slot.map(|registered| registered as usize) == Some(resolver as usize) {
+            *slot = None;
+        }
+    }
+}
+
+fn try_resolve(info: &PageFaultInfo) -> bool {
+    // Copy the resolvers so they can themselves (un)register resolvers
+    let resolvers = *RESOLVERS.lock();
+
+    resolvers.iter().flatten().any(|resolver| resolver(info))
+}
+
+fn print_page_walk(address: u32) {
+    // The fault may have been raised while the page tables were being modified
+    let mut paging = match PAGING.try_lock() {
+        Some(paging) => paging,
+        None => {
+            println!("Page tables are locked, cannot walk them");
+            return;
+        }
+    };
+    let describe = |entry: u32| {
+        let flags = PageFlags::from_bits(entry);
+        let flag_name = |flag, set, unset| if flags.contains(flag) { set } else { unset };
+
+        println!(
+            "    0x{:08x} frame 0x{:08x} {} {} {}",
+            entry,
+            entry & !0xFFF,
+            flag_name(PageFlags::PRESENT, "present", "not-present"),
+            flag_name(PageFlags::WRITABLE, "writable", "read-only"),
+            flag_name(PageFlags::USER, "user", "kernel"),
+        );
+    };
+
+    let (directory_entry, table_entry) = paging.walk(address);
+
+    println!("  Directory entry #{}:", address >> 22);
+    describe(directory_entry);
+    if let Some(table_entry) = table_entry {
+        println!("  Table entry #{}:", (address >> 12) & 0x3FF);
+        describe(table_entry);
+    }
+}
+
+pub fn page_fault_isr(frame: &mut TrapFrame) {
+    let info = PageFaultInfo {
+        address: read_cr2(),
+        error_code: PageFaultErrorCode(frame.error_code),
+        instruction_pointer: frame.eip,
+    };
+
+    if try_resolve(&info) {
+        return;
+    }
+
+    println!(
+        "PageFault at 0x{:08x}: {} (error code {:#x})",
+        info.address, info.error_code, frame.error_code
+    );
+    print_page_walk(info.address);
+    handle_exception(InterruptIndex::PageFault, frame);
+}
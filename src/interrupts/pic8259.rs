@@ -7,8 +7,15 @@ pub const PIC_2_OFFSET: u8 = PIC_1_OFFSET + 8;
 pub static PICS: spin::Mutex<ChainedPics> =
     spin::Mutex::new(ChainedPics::new(PIC_1_OFFSET, PIC_2_OFFSET));
 
+/// Line of the master PIC the slave is chained to.
+pub const CASCADE_IRQ: u8 = 2;
+
 const CMD_INIT: u8 = 0x11;
 const CMD_END_OF_INTERRUPT: u8 = 0x20;
+/// OCW3 making the next command port read return the In-Service Register.
+const CMD_READ_ISR: u8 = 0x0B;
+/// Lowest priority line, raised when a request vanishes before being acknowledged.
+const SPURIOUS_LINE: u8 = 7;
 const MODE_8086: u8 = 0x01;
 
 struct Pic {
@@ -28,6 +35,16 @@ impl Pic {
         self.command.write(CMD_END_OF_INTERRUPT);
     }
 
+    fn read_isr(&mut self) -> u8 {
+        self.command.write(CMD_READ_ISR);
+        self.command.read()
+    }
+
+    /// A real IRQ 7 is in service when its handler runs, a spurious one isn't.
+    fn is_spurious(&mut self, interupt_id: u8) -> bool {
+        interupt_id == self.offset + SPURIOUS_LINE && self.read_isr() & (1 << SPURIOUS_LINE) == 0
+    }
+
     fn read_mask(&mut self) -> u8 {
         self.data.read()
     }
@@ -39,6 +56,8 @@ impl Pic {
 
 pub struct ChainedPics {
     pics: [Pic; 2],
+    /// Spurious interrupts seen on the master and on the slave.
+    spurious_interrupts: [u32; 2],
 }
 
 impl ChainedPics {
@@ -56,6 +75,7 @@ impl ChainedPics {
                     data: Port::new(0xA1),
                 },
             ],
+            spurious_interrupts: [0; 2],
         }
     }
 
@@ -99,6 +119,63 @@ impl ChainedPics {
         self.pics.iter().any(|p| p.handles_interrupt(interrupt_id))
     }
 
+    /// Masks of both PICs, bit n being set when IRQ n is masked.
+    #[allow(dead_code)]
+    pub fn read_masks(&mut self) -> u16 {
+        self.pics[0].read_mask() as u16 | (self.pics[1].read_mask() as u16) << 8
+    }
+
+    pub fn write_masks(&mut self, masks: u16) {
+        self.pics[0].write_mask(masks as u8);
+        self.pics[1].write_mask((masks >> 8) as u8);
+    }
+
+    /// Masks every line, for when the APIC takes over.
+    pub fn disable(&mut self) {
+        self.write_masks(0xFFFF);
+    }
+
+    pub fn mask_irq(&mut self, irq: u8) {
+        let pic = &mut self.pics[usize::from(irq / 8)];
+        let mask = pic.read_mask() | 1 << (irq % 8);
+
+        pic.write_mask(mask);
+    }
+
+    /// Unmasks `irq`, and the cascade line when it belongs to the slave PIC.
+    pub fn unmask_irq(&mut self, irq: u8) {
+        if irq >= 8 {
+            self.unmask_irq(CASCADE_IRQ);
+        }
+
+        let pic = &mut self.pics[usize::from(irq / 8)];
+        let mask = pic.read_mask() & !(1 << (irq % 8));
+
+        pic.write_mask(mask);
+    }
+
+    /// Returns `true` when `interrupt_id` is a spurious IRQ 7 or 15, which must not be handled.
+    ///
+    /// The master gets no end of interrupt for its own spurious IRQ, but it does for a spurious
+    /// IRQ of the slave as it really raised the cascade line.
+    pub fn handle_spurious_interrupt(&mut self, interrupt_id: u8) -> bool {
+        if self.pics[0].is_spurious(interrupt_id) {
+            self.spurious_interrupts[0] += 1;
+            return true;
+        }
+        if self.pics[1].is_spurious(interrupt_id) {
+            self.spurious_interrupts[1] += 1;
+            self.pics[0].end_of_interrupt();
+            return true;
+        }
+        false
+    }
+
+    /// Spurious interrupts counted on the master and on the slave.
+    pub fn spurious_interrupts(&self) -> [u32; 2] {
+        self.spurious_interrupts
+    }
+
     pub fn notify_end_of_interrupt(&mut self, interrupt_id: u8) {
         if self.handles_interrupt(interrupt_id) {
             if self.pics[1].handles_interrupt(interrupt_id) {
@@ -0,0 +1,198 @@
+//! Common entry path of every interrupt vector.
+//!
+//! Each vector has a 16 bytes stub in `trap_stubs` pushing a dummy error code (when the CPU
+//! doesn't push one) and the vector number, then jumping to `trap_common` which saves the rest
+//! of the `TrapFrame` and calls `trap_dispatch`.
+
+use crate::utils::asm::{read_cr0, read_cr2, read_cr3, read_cr4};
+use core::arch::{asm, global_asm};
+use core::fmt;
+use core::sync::atomic::{AtomicPtr, AtomicUsize, Ordering};
+
+const TRAP_STUB_SIZE: u32 = 16;
+
+global_asm!(
+    ".section .text",
+    ".global trap_stubs",
+    ".p2align 4",
+    "trap_stubs:",
+    ".set trap_vector, 0",
+    ".rept 256",
+    "    .p2align 4",
+    // Exceptions for which the CPU pushes an error code
+    "    .if (trap_vector == 8) || (trap_vector >= 10 && trap_vector <= 14) || (trap_vector == 17) || (trap_vector == 21) || (trap_vector == 29) || (trap_vector == 30)",
+    "    .else",
+    "    push 0",
+    "    .endif",
+    "    push trap_vector",
+    "    jmp trap_common",
+    "    .set trap_vector, trap_vector + 1",
+    ".endr",
+    "",
+    "trap_common:",
+    "    pushad",
+    // `push ds` would only push 16 bits, the segments go through EAX to fill whole slots
+    "    mov eax, ds",
+    "    push eax",
+    "    mov eax, es",
+    "    push eax",
+    "    mov eax, fs",
+    "    push eax",
+    "    mov eax, gs",
+    "    push eax",
+    "    mov ax, 0x10",
+    "    mov ds, ax",
+    "    mov es, ax",
+    "    mov fs, ax",
+    "    mov gs, ax",
+    "    cld",
+    "    push esp",
+    "    call trap_dispatch",
+    "    add esp, 4",
+    "    pop eax",
+    "    mov gs, ax",
+    "    pop eax",
+    "    mov fs, ax",
+    "    pop eax",
+    "    mov es, ax",
+    "    pop eax",
+    "    mov ds, ax",
+    "    popad",
+    // Vector number and error code
+    "    add esp, 8",
+    "    iretd",
+);
+
+extern "C" {
+    fn trap_stubs();
+}
+
+/// Address of the entry stub of `vector`, to be put in the IDT.
+pub fn trap_stub(vector: usize) -> u32 {
+    trap_stubs as u32 + vector as u32 * TRAP_STUB_SIZE
+}
+
+/// Every register saved on interrupt entry, in the order `trap_common` and the CPU pushed them.
+#[derive(Debug, Clone, Copy)]
+#[repr(C)]
+pub struct TrapFrame {
+    pub gs: u32,
+    pub fs: u32,
+    pub es: u32,
+    pub ds: u32,
+    pub edi: u32,
+    pub esi: u32,
+    pub ebp: u32,
+    /// Value of ESP when `pushad` ran, use `esp()` for the interrupted stack pointer.
+    kernel_esp: u32,
+    pub ebx: u32,
+    pub edx: u32,
+    pub ecx: u32,
+    pub eax: u32,
+    pub vector: u32,
+    pub error_code: u32,
+    pub eip: u32,
+    pub cs: u32,
+    pub eflags: u32,
+    /// Only pushed by the CPU when the interrupt came from a lower privilege level.
+    user_esp: u32,
+    user_ss: u32,
+}
+
+impl TrapFrame {
+    fn is_from_user_mode(&self) -> bool {
+        self.cs & 0b11 != 0
+    }
+
+    /// Stack pointer of the interrupted code.
+    pub fn esp(&self) -> u32 {
+        if self.is_from_user_mode() {
+            self.user_esp
+        } else {
+            &self.user_esp as *const u32 as u32
+        }
+    }
+
+    /// Stack segment of the interrupted code.
+    pub fn ss(&self) -> u32 {
+        let ss: u32;
+
+        if self.is_from_user_mode() {
+            return self.user_ss & 0xFFFF;
+        }
+        // Same privilege level, the stack segment didn't change
+        unsafe {
+            asm!("mov {:x}, ss", out(reg) ss, options(nomem, nostack));
+        }
+        ss & 0xFFFF
+    }
+}
+
+impl fmt::Display for TrapFrame {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "Vector {:#04x} error code {:#010x}",
+            self.vector, self.error_code
+        )?;
+        writeln!(
+            f,
+            "EAX={:08x} EBX={:08x} ECX={:08x} EDX={:08x}",
+            self.eax, self.ebx, self.ecx, self.edx
+        )?;
+        writeln!(
+            f,
+            "ESI={:08x} EDI={:08x} EBP={:08x} ESP={:08x}",
+            self.esi,
+            self.edi,
+            self.ebp,
+            self.esp()
+        )?;
+        writeln!(f, "EIP={:08x} EFLAGS={:08x}", self.eip, self.eflags)?;
+        writeln!(
+            f,
+            "CS={:04x} DS={:04x} ES={:04x} FS={:04x} GS={:04x} SS={:04x}",
+            self.cs & 0xFFFF,
+            self.ds & 0xFFFF,
+            self.es & 0xFFFF,
+            self.fs & 0xFFFF,
+            self.gs & 0xFFFF,
+            self.ss()
+        )?;
+        write!(
+            f,
+            "CR0={:08x} CR2={:08x} CR3={:08x} CR4={:08x}",
+            read_cr0(),
+            read_cr2(),
+            read_cr3(),
+            read_cr4()
+        )
+    }
+}
+
+/// Frame of the interrupt being handled, so that the panic screen can print it.
+static CURRENT_TRAP_FRAME: AtomicPtr<TrapFrame> = AtomicPtr::new(core::ptr::null_mut());
+
+pub fn current_trap_frame() -> Option<&'static TrapFrame> {
+    unsafe { CURRENT_TRAP_FRAME.load(Ordering::SeqCst).as_ref() }
+}
+
+/// Number of interrupts being handled, nested ones included.
+static TRAP_DEPTH: AtomicUsize = AtomicUsize::new(0);
+
+/// 0 outside of interrupt handlers, 1 inside the handler of an interrupt of the kernel code, and
+/// more in nested handlers.
+pub fn trap_depth() -> usize {
+    TRAP_DEPTH.load(Ordering::SeqCst)
+}
+
+#[no_mangle]
+extern "C" fn trap_dispatch(frame: &mut TrapFrame) {
+    let previous = CURRENT_TRAP_FRAME.swap(frame, Ordering::SeqCst);
+    TRAP_DEPTH.fetch_add(1, Ordering::SeqCst);
+
+    crate::interrupts::dispatch(frame);
+
+    TRAP_DEPTH.fetch_sub(1, Ordering::SeqCst);
+    CURRENT_TRAP_FRAME.store(previous, Ordering::SeqCst);
+}
@@ -0,0 +1,61 @@
+//! Dead keys, which type nothing but accent the next letter. Keymaps mark them with the matching
+//! combining diacritical mark.
+
+pub const GRAVE: char = '\u{300}';
+pub const CIRCUMFLEX: char = '\u{302}';
+pub const TILDE: char = '\u{303}';
+pub const DIAERESIS: char = '\u{308}';
+
+/// Letters each dead key composes with, and the characters they give, in the same order.
+const COMPOSITIONS: &[(char, &str, &str)] = &[
+    (GRAVE, "aeiouAEIOU", "àèìòùÀÈÌÒÙ"),
+    (CIRCUMFLEX, "aeiouAEIOU", "âêîôûÂÊÎÔÛ"),
+    (TILDE, "anoANO", "ãñõÃÑÕ"),
+    (DIAERESIS, "aeiouyAEIOUY", "äëïöüÿÄËÏÖÜŸ"),
+];
+
+pub fn is_dead_key(c: char) -> bool {
+    ('\u{300}'..='\u{36F}').contains(&c)
+}
+
+/// Character typed by the dead key alone, when followed by space or by itself.
+pub fn spacing(dead_key: char) -> char {
+    match dead_key {
+        GRAVE => '`',
+        CIRCUMFLEX => '^',
+        TILDE => '~',
+        // The VGA font has no '¨'
+        DIAERESIS => '"',
+        other => other,
+    }
+}
+
+/// Accented form of `letter`, if the dead key applies to it.
+pub fn compose(dead_key: char, letter: char) -> Option<char> {
+    let &(_, letters, composed) = COMPOSITIONS.iter().find(|&&(key, _, _)| key == dead_key)?;
+    let index = letters.chars().position(|c| c == letter)?;
+
+    composed.chars().nth(index)
+}
+
+/// Feeds a character typed on the keyboard through the `pending` dead key, returns the accent
+/// to emit before the character, and the character to emit.
+pub fn apply_dead_key(pending: &mut Option<char>, c: char) -> (Option<char>, Option<char>) {
+    match (pending.take(), c) {
+        (None, c) if is_dead_key(c) => {
+            *pending = Some(c);
+            (None, None)
+        }
+        (None, c) => (None, Some(c)),
+        (Some(dead_key), c) if c == dead_key || c == ' ' => (None, Some(spacing(dead_key))),
+        (Some(dead_key), c) if is_dead_key(c) => {
+            *pending = Some(c);
+            (None, Some(spacing(dead_key)))
+        }
+        // A letter without an accented form is typed after the accent, as on other systems
+        (Some(dead_key), c) => match compose(dead_key, c) {
+            Some(composed) => (None, Some(composed)),
+            None => (Some(spacing(dead_key)), Some(c)),
+        },
+    }
+}
@@ -1,26 +1,28 @@
+use super::compose::{CIRCUMFLEX, DIAERESIS, GRAVE, TILDE};
+use super::Keymap;
 use crate::create_keymap_array;
 
-pub const KEYMAP_FR: [[u8; 2]; 0x80] = create_keymap_array!(
-    [0, 0];
+pub const KEYMAP_FR: Keymap = create_keymap_array!(
+    ['\0'; 4];
     0x80;
 
     0x1 => [0x1B as char, 0x1B as char],
     0x2 => ['&', '1'],
-    0x3 => ['é', '2'],
-    0x4 => ['"', '3'],
-    0x5 => ['\'', '4'],
-    0x6 => ['(', '5'],
-    0x7 => ['-', '6'],
-    0x8 => ['è', '7'],
-    0x9 => ['_', '8'],
-    0xA => ['ç', '9'],
-    0xB => ['à', '0'],
-    0xC => [')', '°'],
-    0xD => ['=', '+'],
+    0x3 => ['é', '2', TILDE],
+    0x4 => ['"', '3', '#'],
+    0x5 => ['\'', '4', '{'],
+    0x6 => ['(', '5', '['],
+    0x7 => ['-', '6', '|'],
+    0x8 => ['è', '7', GRAVE],
+    0x9 => ['_', '8', '\\'],
+    0xA => ['ç', '9', '^'],
+    0xB => ['à', '0', '@'],
+    0xC => [')', '°', ']'],
+    0xD => ['=', '+', '}'],
 
     0x10 => ['a', 'A'],
     0x11 => ['z', 'Z'],
-    0x12 => ['e', 'E'],
+    0x12 => ['e', 'E', '€'],
     0x13 => ['r', 'R'],
     0x14 => ['t', 'T'],
     0x15 => ['y', 'Y'],
@@ -28,8 +30,8 @@ pub const KEYMAP_FR: [[u8; 2]; 0x80] = create_keymap_array!(
     0x17 => ['i', 'I'],
     0x18 => ['o', 'O'],
     0x19 => ['p', 'P'],
-    0x1A => ['^', '¨'],
-    0x1B => ['$', '£'],
+    0x1A => [CIRCUMFLEX, DIAERESIS],
+    0x1B => ['$', '£', '¤'],
 
     0x1E => ['q', 'Q'],
     0x1F => ['s', 'S'],
@@ -1,7 +1,8 @@
+use super::Keymap;
 use crate::create_keymap_array;
 
-pub const KEYMAP_US: [[u8; 2]; 0x80] = create_keymap_array!(
-    [0, 0];
+pub const KEYMAP_US: Keymap = create_keymap_array!(
+    ['\0'; 4];
     0x80;
 
     0x1 => [0x1B as char, 0x1B as char],
@@ -1,96 +1,179 @@
+pub mod compose;
 mod keymap_fr;
 mod keymap_us;
+pub mod scancode;
+
+use crate::utils::ring_buffer::RingBuffer;
+use scancode::{KeyCode, RawKeyEvent, ScancodeDecoder};
 
 #[macro_export]
 macro_rules! create_keymap_array {
-    // value [0] is normal key, [1] is shifted key
-    ($default:expr; $len:expr; $($index:expr => $value:expr),*) => {{
+    // value [0] is normal key, [1] is shifted key, [2] is AltGr key and [3] is Shift+AltGr key,
+    // the AltGr columns can be omitted
+    ($default:expr; $len:expr; $($index:expr => [$($value:expr),+]),*) => {{
         let mut arr = [$default; $len];
         $(
-            let as_bytes: [u8; 2] = [$value[0] as u8, $value[1] as u8];
-            arr[$index] = as_bytes;
+            let values = [$($value as char),+];
+            let mut layer = 0;
+            while layer < values.len() {
+                arr[$index][layer] = values[layer];
+                layer += 1;
+            }
         )*
         arr
     }};
 }
 
+/// Character typed by each key code in every layer, `'\0'` when there is none.
+pub type Keymap = [[char; LAYER_COUNT]; 0x80];
+
+const LAYER_SHIFT: usize = 1;
+const LAYER_ALTGR: usize = 2;
+const LAYER_COUNT: usize = 4;
+
 #[allow(dead_code)]
 pub enum KeymapLanguage {
     US,
     FR,
 }
 
-pub struct KeyboardState {
-    pub lang: KeymapLanguage,
+/// Modifier keys held, and Caps Lock.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Modifiers {
     pub shift: bool,
     pub ctrl: bool,
     pub alt: bool,
+    /// Right Alt, selecting the third and fourth layers of the keymap.
+    pub altgr: bool,
     pub capslock: bool,
 }
 
-pub fn handle_scancode(scancode: u8, state: &mut KeyboardState, output: &mut [u8]) {
-    let keycode = scancode & 0b01111111;
-    let is_release = scancode & 0x80 != 0;
-    let is_pressed = !is_release;
-    let mut i = 0;
+/// A key pressed or released, with the character it types on the current layout.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeyEvent {
+    pub code: KeyCode,
+    pub pressed: bool,
+    /// Modifiers in effect for this key, including the change it makes.
+    pub modifiers: Modifiers,
+    /// Character given by the layout and any dead key typed before, regardless of Ctrl and Alt
+    /// which the consumer interprets.
+    pub char: Option<char>,
+    /// Accent of a previous dead key that doesn't compose with `char`, to type before it.
+    pub accent: Option<char>,
+}
 
-    let mut write_change = |c: char| {
-        output[i] = c as u8;
-        i += 1;
-    };
+pub struct KeyboardState {
+    pub lang: KeymapLanguage,
+    pub modifiers: Modifiers,
+    /// Dead key waiting for the next character.
+    pub dead_key: Option<char>,
+    pub decoder: ScancodeDecoder,
+}
 
-    match keycode {
-        0x1d => state.ctrl = is_pressed,
-        0x38 => state.alt = is_pressed,
-        0x2a | 0x36 => state.shift = is_pressed,
-        0x3a => {
-            if is_release {
-                state.capslock = !state.capslock
-            }
+pub static mut KEYBOARD_STATE: KeyboardState = KeyboardState {
+    lang: KeymapLanguage::US,
+    modifiers: Modifiers {
+        shift: false,
+        ctrl: false,
+        alt: false,
+        altgr: false,
+        capslock: false,
+    },
+    dead_key: None,
+    decoder: ScancodeDecoder::new(),
+};
+
+/// Bytes read by the keyboard interrupt, decoded outside of it.
+pub static SCANCODES: RingBuffer<u8, 128> = RingBuffer::new();
+
+/// Feeds a byte read from the keyboard controller, returns the key event it completes.
+pub fn handle_scancode(scancode: u8, state: &mut KeyboardState) -> Option<KeyEvent> {
+    let event = state.decoder.decode(scancode)?;
+
+    Some(handle_key_event(event, state))
+}
+
+fn handle_key_event(event: RawKeyEvent, state: &mut KeyboardState) -> KeyEvent {
+    let is_pressed = event.pressed;
+    let modifiers = &mut state.modifiers;
+
+    let char = match event.code {
+        KeyCode::LEFT_CTRL | KeyCode::RightCtrl => {
+            modifiers.ctrl = is_pressed;
+            None
         }
-        0x1C => {
-            if is_pressed {
-                write_change('\n');
-            }
+        KeyCode::LEFT_ALT => {
+            modifiers.alt = is_pressed;
+            None
         }
-        0x0E => {
-            if is_pressed {
-                write_change(0x08 as char);
+        KeyCode::RightAlt => {
+            modifiers.altgr = is_pressed;
+            None
+        }
+        KeyCode::LEFT_SHIFT | KeyCode::RIGHT_SHIFT => {
+            modifiers.shift = is_pressed;
+            None
+        }
+        KeyCode::CAPS_LOCK => {
+            if !is_pressed {
+                modifiers.capslock = !modifiers.capslock
             }
+            None
         }
-        _ => {
-            if is_pressed {
-                if state.ctrl && state.alt && keycode == 0x10 {
-                    state.lang = match state.lang {
-                        KeymapLanguage::US => KeymapLanguage::FR,
-                        KeymapLanguage::FR => KeymapLanguage::US,
-                    };
-                }
-
-                let keymap_index_mode = match (state.shift | state.capslock, state.alt) {
-                    (true, _) => 1,
-                    // (_, true) => 2,
-                    _ => 0,
+        KeyCode::ENTER | KeyCode::KeypadEnter => Some('\n'),
+        KeyCode::BACKSPACE => Some('\x08'),
+        KeyCode::KeypadSlash => Some('/'),
+        KeyCode::Base(keycode) => {
+            if is_pressed && modifiers.ctrl && modifiers.alt && keycode == 0x10 {
+                state.lang = match state.lang {
+                    KeymapLanguage::US => KeymapLanguage::FR,
+                    KeymapLanguage::FR => KeymapLanguage::US,
                 };
+            }
 
-                let keymap = match state.lang {
-                    KeymapLanguage::US => keymap_us::KEYMAP_US,
-                    KeymapLanguage::FR => keymap_fr::KEYMAP_FR,
-                };
+            let keymap = match state.lang {
+                KeymapLanguage::US => &keymap_us::KEYMAP_US,
+                KeymapLanguage::FR => &keymap_fr::KEYMAP_FR,
+            };
+            let layers = &keymap[keycode as usize];
+            let shift_layer = match modifiers.shift | modifiers.capslock {
+                true => LAYER_SHIFT,
+                false => 0,
+            };
+
+            // CapsLock only shifts the letters of the AltGr layer, and a key without a Shift+AltGr
+            // character types its AltGr one
+            let altgr_shifted =
+                modifiers.shift || (modifiers.capslock && layers[LAYER_ALTGR].is_alphabetic());
+            let altgr_key = match layers[LAYER_ALTGR + LAYER_SHIFT] {
+                key if altgr_shifted && key != '\0' => key,
+                _ => layers[LAYER_ALTGR],
+            };
 
-                let mut key = keymap[keycode as usize][keymap_index_mode];
-
-                if key != 0 {
-                    if state.ctrl {
-                        if !key.is_ascii_alphabetic() {
-                            return;
-                        }
-                        write_change('^');
-                        key.make_ascii_uppercase();
-                    }
-                    write_change(key as char);
-                }
+            // Keys without an AltGr character type their usual one, as on US layouts
+            let key = match altgr_key {
+                key if modifiers.altgr && key != '\0' => key,
+                _ => layers[shift_layer],
+            };
+
+            match key {
+                '\0' => None,
+                key => Some(key),
             }
         }
+        _ => None,
+    };
+
+    let (accent, char) = match char {
+        Some(c) if is_pressed => compose::apply_dead_key(&mut state.dead_key, c),
+        char => (None, char),
+    };
+
+    KeyEvent {
+        code: event.code,
+        pressed: is_pressed,
+        modifiers: state.modifiers,
+        char,
+        accent,
     }
 }
@@ -0,0 +1,158 @@
+//! Decoder of the PS/2 scan code set 1, turning the bytes read from the controller into key
+//! presses and releases. Keys added after the XT keyboard send an 0xE0 prefix before their code,
+//! and Pause sends a whole 0xE1 sequence.
+//! https://wiki.osdev.org/PS/2_Keyboard#Scan_Code_Set_1
+
+const EXTENDED_PREFIX: u8 = 0xE0;
+const PAUSE_PREFIX: u8 = 0xE1;
+/// Bytes following the 0xE1 prefix: 1D 45 E1 9D C5, Pause has no release code.
+const PAUSE_SEQUENCE_LENGTH: u8 = 5;
+const RELEASE: u8 = 0x80;
+
+// Controller replies and errors, never part of a key code
+const KEY_DETECTION_ERROR: u8 = 0x00;
+const ECHO: u8 = 0xEE;
+const ACKNOWLEDGE: u8 = 0xFA;
+const RESEND: u8 = 0xFE;
+const BUFFER_OVERRUN: u8 = 0xFF;
+
+/// Sent around Print Screen and the navigation keys, as if the keyboard pressed or released
+/// Shift to cancel its effect on old software.
+const FAKE_LEFT_SHIFT: u8 = 0x2A;
+const FAKE_RIGHT_SHIFT: u8 = 0x36;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyCode {
+    /// A key of the XT keyboard, by its code without prefix: the main block, function keys and
+    /// numpad.
+    Base(u8),
+    Up,
+    Down,
+    Left,
+    Right,
+    Home,
+    End,
+    PageUp,
+    PageDown,
+    Insert,
+    Delete,
+    RightCtrl,
+    /// Right Alt, AltGr on international layouts.
+    RightAlt,
+    LeftGui,
+    RightGui,
+    Menu,
+    KeypadEnter,
+    KeypadSlash,
+    PrintScreen,
+    Pause,
+    /// Another 0xE0 prefixed key, such as the multimedia and power keys.
+    Extended(u8),
+}
+
+impl KeyCode {
+    pub const BACKSPACE: KeyCode = KeyCode::Base(0x0E);
+    pub const ENTER: KeyCode = KeyCode::Base(0x1C);
+    pub const LEFT_CTRL: KeyCode = KeyCode::Base(0x1D);
+    pub const LEFT_SHIFT: KeyCode = KeyCode::Base(0x2A);
+    pub const RIGHT_SHIFT: KeyCode = KeyCode::Base(0x36);
+    pub const LEFT_ALT: KeyCode = KeyCode::Base(0x38);
+    pub const CAPS_LOCK: KeyCode = KeyCode::Base(0x3A);
+
+    fn from_extended(code: u8) -> KeyCode {
+        match code {
+            0x1C => KeyCode::KeypadEnter,
+            0x1D => KeyCode::RightCtrl,
+            0x35 => KeyCode::KeypadSlash,
+            0x37 => KeyCode::PrintScreen,
+            0x38 => KeyCode::RightAlt,
+            0x47 => KeyCode::Home,
+            0x48 => KeyCode::Up,
+            0x49 => KeyCode::PageUp,
+            0x4B => KeyCode::Left,
+            0x4D => KeyCode::Right,
+            0x4F => KeyCode::End,
+            0x50 => KeyCode::Down,
+            0x51 => KeyCode::PageDown,
+            0x52 => KeyCode::Insert,
+            0x53 => KeyCode::Delete,
+            0x5B => KeyCode::LeftGui,
+            0x5C => KeyCode::RightGui,
+            0x5D => KeyCode::Menu,
+            code => KeyCode::Extended(code),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RawKeyEvent {
+    pub code: KeyCode,
+    pub pressed: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum DecoderState {
+    Idle,
+    /// 0xE0 was received, the next byte is an extended key code.
+    Extended,
+    /// Bytes left in the Pause sequence.
+    Pause(u8),
+}
+
+pub struct ScancodeDecoder {
+    state: DecoderState,
+}
+
+impl ScancodeDecoder {
+    pub const fn new() -> ScancodeDecoder {
+        ScancodeDecoder {
+            state: DecoderState::Idle,
+        }
+    }
+
+    /// Feeds one byte read from the keyboard, returns the key event it completes.
+    pub fn decode(&mut self, scancode: u8) -> Option<RawKeyEvent> {
+        match self.state {
+            DecoderState::Pause(remaining) => {
+                if remaining > 1 {
+                    self.state = DecoderState::Pause(remaining - 1);
+                    return None;
+                }
+                self.state = DecoderState::Idle;
+
+                return Some(RawKeyEvent {
+                    code: KeyCode::Pause,
+                    pressed: true,
+                });
+            }
+            DecoderState::Extended => {
+                self.state = DecoderState::Idle;
+
+                return match scancode & !RELEASE {
+                    FAKE_LEFT_SHIFT | FAKE_RIGHT_SHIFT => None,
+                    code => Some(RawKeyEvent {
+                        code: KeyCode::from_extended(code),
+                        pressed: scancode & RELEASE == 0,
+                    }),
+                };
+            }
+            DecoderState::Idle => {}
+        }
+
+        match scancode {
+            EXTENDED_PREFIX => {
+                self.state = DecoderState::Extended;
+                None
+            }
+            PAUSE_PREFIX => {
+                self.state = DecoderState::Pause(PAUSE_SEQUENCE_LENGTH);
+                None
+            }
+            KEY_DETECTION_ERROR | ECHO | ACKNOWLEDGE | RESEND | BUFFER_OVERRUN => None,
+            _ => Some(RawKeyEvent {
+                code: KeyCode::Base(scancode & !RELEASE),
+                pressed: scancode & RELEASE == 0,
+            }),
+        }
+    }
+}
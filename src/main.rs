@@ -1,54 +1,118 @@
 #![no_std]
 #![no_main]
 #![no_builtins]
-#![feature(abi_x86_interrupt)]
+#![feature(alloc_error_handler)]
+#![feature(inline_const)]
+
+extern crate alloc;
 
 use core::arch::asm;
 use core::panic::PanicInfo;
 use vga_buffer::*;
 
+mod acpi;
 mod cli;
 mod gdt;
 mod interrupts;
 mod io;
 mod keyboard;
+mod memory;
+mod multiboot;
 mod panic;
+mod serial;
+mod time;
 mod utils;
 mod vga_buffer;
 
 #[allow(dead_code)]
 extern "C" {
+    fn stack_guard();
     fn stack_bottom();
     fn stack_top();
+    fn kernel_start();
+    fn kernel_end();
 }
 
 #[no_mangle]
-pub extern "C" fn main() -> ! {
-    let gdt = gdt::GlobalDescriptorTable::init();
-    gdt.install();
+pub extern "C" fn main(multiboot_magic: u32, multiboot_info_addr: u32) -> ! {
+    let boot_info = match multiboot::init(multiboot_magic, multiboot_info_addr) {
+        Some(boot_info) => boot_info,
+        None => panic!("Invalid Multiboot2 magic: 0x{:x}", multiboot_magic),
+    };
+
+    let serial_console = boot_info.command_line_option("console") == Some("serial");
+    match serial::init(serial::DEFAULT_BAUD_RATE) {
+        Ok(()) => serial::set_mirror_console(serial_console),
+        Err(error) => println!("COM1 unavailable: {:?}", error),
+    }
+
+    memory::init(boot_info);
+
+    gdt::init();
 
     interrupts::init_idt();
-    interrupts::pic8259::PICS.lock().initialize();
+    let interrupt_controller = match boot_info.command_line_option("irqchip") {
+        Some("apic") => interrupts::irq::InterruptController::Apic,
+        _ => interrupts::irq::InterruptController::Pic8259,
+    };
+    println!(
+        "Interrupt controller: {:?}",
+        interrupts::irq::init(interrupt_controller)
+    );
+    let timer_frequency = boot_info
+        .command_line_option("timer_hz")
+        .and_then(|frequency| frequency.parse().ok())
+        .unwrap_or(time::pit::DEFAULT_FREQUENCY);
+    time::pit::init(timer_frequency);
+    match time::tsc::init() {
+        Some(frequency) => println!("TSC frequency: {} kHz", frequency / 1000),
+        None => println!("TSC not supported"),
+    }
+    interrupts::irq::register_handler(interrupts::irq::TIMER_IRQ, interrupts::isr::timer_isr)
+        .expect("Timer IRQ already registered");
+    interrupts::irq::register_handler(
+        interrupts::irq::KEYBOARD_IRQ,
+        interrupts::isr::keyboard_interrupt_handler,
+    )
+    .expect("Keyboard IRQ already registered");
+    if serial::is_console_mirrored() {
+        interrupts::irq::register_handler(
+            serial::COM1_IRQ,
+            interrupts::isr::serial_interrupt_handler,
+        )
+        .expect("COM1 IRQ already registered");
+        serial::enable_receive_interrupt();
+    }
+    if let Some(frequency) = boot_info
+        .command_line_option("rtc_hz")
+        .and_then(|frequency| frequency.parse().ok())
+    {
+        match time::rtc::enable_periodic_interrupt(frequency) {
+            Ok(frequency) => println!("RTC periodic interrupt: {} Hz", frequency),
+            Err(error) => println!("RTC periodic interrupt: {:?}", error),
+        }
+    }
+    println!("Date: {}", time::rtc::read());
 
     let v = 42;
 
-    println!("GDT pointer: {:?}", gdt.get_gdt_pointer());
+    println!("GDT pointer: {:?}", gdt::get_gdt_pointer());
 
     println!("Stack bottom: 0x{:x}", stack_bottom as u32);
     println!("Stack top: 0x{:x}", stack_top as u32);
     println!("Our variable: {:p}", &v);
 
     println!("GDT dump:");
-    gdt.print();
+    gdt::print();
 
     println!("Stack dump:");
     hexdump(unsafe { (stack_top as *const u8).offset(-0x80) }, 0x80);
 
+    cli::init();
+
     unsafe {
         asm!("sti");
     }
 
-    loop {
-        halt!();
-    }
+    cli::run();
 }
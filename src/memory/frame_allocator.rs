@@ -0,0 +1,216 @@
+use crate::memory::PAGE_SIZE;
+use crate::multiboot::{BootInfo, MemoryRegionKind};
+use crate::{kernel_end, kernel_start, stack_bottom, stack_top};
+use spin::Mutex;
+
+/// Number of 4 KiB frames in the 32-bit physical address space.
+const FRAME_COUNT: usize = 1 << 20;
+const BITMAP_LENGTH: usize = FRAME_COUNT / 32;
+
+/// Everything below 1 MiB is left to the BIOS, the VGA buffer and the GDT copy.
+const LOW_MEMORY_END: u32 = 0x100000;
+
+pub static FRAME_ALLOCATOR: Mutex<BitmapFrameAllocator> = Mutex::new(BitmapFrameAllocator::new());
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FrameError {
+    /// The frame at this address isn't in an available region of the memory map.
+    NotUsable(u32),
+    /// The frame at this address is already free.
+    AlreadyFree(u32),
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct FrameStats {
+    pub total: usize,
+    pub used: usize,
+    pub free: usize,
+}
+
+/// Physical frame allocator, a set bit means the frame is free. Frames start out used so
+/// that the bitmap lives in `.bss` and only the memory map regions are handed out.
+pub struct BitmapFrameAllocator {
+    bitmap: [u32; BITMAP_LENGTH],
+    /// A set bit means the frame is in an available region, and counted in `total`.
+    usable: [u32; BITMAP_LENGTH],
+    total: usize,
+    used: usize,
+    next_free: usize,
+}
+
+#[allow(dead_code)]
+impl BitmapFrameAllocator {
+    const fn new() -> Self {
+        BitmapFrameAllocator {
+            bitmap: [0; BITMAP_LENGTH],
+            usable: [0; BITMAP_LENGTH],
+            total: 0,
+            used: 0,
+            next_free: 0,
+        }
+    }
+
+    fn is_used(&self, frame: usize) -> bool {
+        self.bitmap[frame / 32] & (1 << (frame % 32)) == 0
+    }
+
+    fn set_used(&mut self, frame: usize) {
+        self.bitmap[frame / 32] &= !(1 << (frame % 32));
+    }
+
+    fn set_free(&mut self, frame: usize) {
+        self.bitmap[frame / 32] |= 1 << (frame % 32);
+    }
+
+    fn is_usable(&self, frame: usize) -> bool {
+        frame < FRAME_COUNT && self.usable[frame / 32] & (1 << (frame % 32)) != 0
+    }
+
+    /// Hands out every frame entirely contained in `[start; end[`.
+    fn add_region(&mut self, start: u64, end: u64) {
+        let first = start.div_ceil(PAGE_SIZE as u64);
+        let last = core::cmp::min(end / PAGE_SIZE as u64, FRAME_COUNT as u64);
+
+        for frame in first..last {
+            let frame = frame as usize;
+
+            if !self.is_usable(frame) {
+                self.usable[frame / 32] |= 1 << (frame % 32);
+                self.set_free(frame);
+                self.total += 1;
+            }
+        }
+    }
+
+    /// Marks every frame overlapping `[start; end[` as used.
+    fn reserve_region(&mut self, start: u32, end: u32) {
+        let first = start as usize / PAGE_SIZE;
+        let last = (end as usize).div_ceil(PAGE_SIZE);
+
+        for frame in first..core::cmp::min(last, FRAME_COUNT) {
+            if !self.is_used(frame) {
+                self.set_used(frame);
+                self.used += 1;
+            }
+        }
+    }
+
+    pub fn init(&mut self, boot_info: &BootInfo) {
+        let memory_map = boot_info
+            .memory_map
+            .expect("Multiboot2 memory map is required to set up the frame allocator");
+
+        for entry in memory_map.iter() {
+            if entry.kind() == MemoryRegionKind::Available {
+                self.add_region(entry.base_addr, entry.base_addr + entry.length);
+            }
+        }
+
+        self.reserve_region(0, LOW_MEMORY_END);
+        self.reserve_region(kernel_start as u32, kernel_end as u32);
+        self.reserve_region(stack_bottom as u32, stack_top as u32);
+        self.reserve_region(boot_info.start, boot_info.end());
+        for module in boot_info.modules() {
+            self.reserve_region(module.start, module.end);
+        }
+        // GRUB may load sections such as the string table outside of the kernel image
+        if let Some(sections) = boot_info.elf_sections {
+            for section in sections.iter().filter(|s| s.addr != 0) {
+                self.reserve_region(section.addr, section.addr + section.size);
+            }
+        }
+    }
+
+    /// Returns the physical address of a free 4 KiB frame.
+    pub fn alloc(&mut self) -> Option<u32> {
+        self.alloc_contiguous(1)
+    }
+
+    /// Returns the physical address of `count` free and physically contiguous frames.
+    pub fn alloc_contiguous(&mut self, count: usize) -> Option<u32> {
+        if count == 0 || self.total - self.used < count {
+            return None;
+        }
+
+        let mut run_start = self.next_free;
+        let mut run_length = 0;
+
+        for frame in self.next_free..FRAME_COUNT {
+            if self.is_used(frame) {
+                run_length = 0;
+                continue;
+            }
+            if run_length == 0 {
+                run_start = frame;
+            }
+            run_length += 1;
+
+            if run_length == count {
+                for allocated in run_start..run_start + count {
+                    self.set_used(allocated);
+                }
+                self.used += count;
+                if run_start == self.next_free {
+                    self.next_free = run_start + count;
+                }
+                return Some((run_start * PAGE_SIZE) as u32);
+            }
+        }
+
+        None
+    }
+
+    /// Gives back a frame previously returned by `alloc`.
+    pub fn free(&mut self, addr: u32) -> Result<(), FrameError> {
+        self.free_contiguous(addr, 1)
+    }
+
+    /// Gives back `count` frames, nothing is freed if one of them isn't a used frame of the
+    /// available memory.
+    pub fn free_contiguous(&mut self, addr: u32, count: usize) -> Result<(), FrameError> {
+        let first = addr as usize / PAGE_SIZE;
+        let frame_addr = |frame: usize| (frame * PAGE_SIZE) as u32;
+
+        for frame in first..first + count {
+            if !self.is_usable(frame) {
+                return Err(FrameError::NotUsable(frame_addr(frame)));
+            }
+            if !self.is_used(frame) {
+                return Err(FrameError::AlreadyFree(frame_addr(frame)));
+            }
+        }
+
+        for frame in first..first + count {
+            self.set_free(frame);
+        }
+        self.used -= count;
+        self.next_free = core::cmp::min(self.next_free, first);
+        Ok(())
+    }
+
+    pub fn stats(&self) -> FrameStats {
+        FrameStats {
+            total: self.total,
+            used: self.used,
+            free: self.total - self.used,
+        }
+    }
+}
+
+pub fn alloc_frame() -> Option<u32> {
+    FRAME_ALLOCATOR.lock().alloc()
+}
+
+#[allow(dead_code)]
+pub fn alloc_frames(count: usize) -> Option<u32> {
+    FRAME_ALLOCATOR.lock().alloc_contiguous(count)
+}
+
+pub fn free_frame(addr: u32) -> Result<(), FrameError> {
+    FRAME_ALLOCATOR.lock().free(addr)
+}
+
+#[allow(dead_code)]
+pub fn free_frames(addr: u32, count: usize) -> Result<(), FrameError> {
+    FRAME_ALLOCATOR.lock().free_contiguous(addr, count)
+}
@@ -0,0 +1,223 @@
+//! Kernel heap: a first-fit allocator over an address-ordered free list whose neighbouring
+//! blocks are merged back together on deallocation.
+
+use crate::memory::frame_allocator::{alloc_frame, free_frame};
+use crate::memory::paging::{PageFlags, PAGING};
+use crate::memory::PAGE_SIZE;
+use core::alloc::{GlobalAlloc, Layout};
+use core::mem::{align_of, size_of};
+use core::ptr::null_mut;
+use spin::Mutex;
+
+pub const HEAP_START: usize = 0xD000_0000;
+pub const HEAP_INITIAL_SIZE: usize = 0x10_0000;
+pub const HEAP_MAX_SIZE: usize = 0x400_0000;
+
+#[global_allocator]
+static ALLOCATOR: KernelHeap = KernelHeap(Mutex::new(FreeListAllocator::new()));
+
+#[alloc_error_handler]
+fn alloc_error(layout: Layout) -> ! {
+    panic!("Kernel heap allocation failed: {:?}", layout)
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct HeapStats {
+    pub size: usize,
+    pub used: usize,
+    pub free: usize,
+}
+
+/// Header written at the start of every free block.
+struct FreeBlock {
+    size: usize,
+    next: *mut FreeBlock,
+}
+
+/// Every block address and size is a multiple of this, so a split always leaves room for a
+/// `FreeBlock` header.
+const BLOCK_ALIGN: usize = size_of::<FreeBlock>();
+
+fn align_up(addr: usize, align: usize) -> usize {
+    (addr + align - 1) & !(align - 1)
+}
+
+pub struct FreeListAllocator {
+    head: *mut FreeBlock,
+    start: usize,
+    end: usize,
+    used: usize,
+}
+
+// The allocator is only ever reached through the Mutex of `KernelHeap`
+unsafe impl Send for FreeListAllocator {}
+
+impl FreeListAllocator {
+    const fn new() -> Self {
+        FreeListAllocator {
+            head: null_mut(),
+            start: HEAP_START,
+            end: HEAP_START,
+            used: 0,
+        }
+    }
+
+    /// Maps enough new pages at the end of the heap to hold at least `size` bytes.
+    fn grow(&mut self, size: usize) -> bool {
+        let size = align_up(size, PAGE_SIZE);
+
+        if self.end + size > self.start + HEAP_MAX_SIZE {
+            return false;
+        }
+
+        let mut paging = PAGING.lock();
+        let flags = PageFlags::PRESENT | PageFlags::WRITABLE;
+
+        for page in (self.end..self.end + size).step_by(PAGE_SIZE) {
+            let mapped = alloc_frame().map(|frame| {
+                let result = paging.map(page as u32, frame, flags);
+                if result.is_err() {
+                    free_frame(frame).expect("Frame just allocated");
+                }
+                result.is_ok()
+            });
+
+            if mapped != Some(true) {
+                // Give back what was mapped so far, the heap end didn't move
+                for mapped_page in (self.end..page).step_by(PAGE_SIZE) {
+                    if let Some(frame) = paging.unmap(mapped_page as u32) {
+                        free_frame(frame).expect("Heap frame was allocated");
+                    }
+                }
+                return false;
+            }
+        }
+        drop(paging);
+
+        let region = self.end;
+        self.end += size;
+        unsafe { self.insert_free(region, size) };
+        true
+    }
+
+    /// Puts `[addr; addr + size[` back in the free list, merging it with its neighbours.
+    unsafe fn insert_free(&mut self, addr: usize, size: usize) {
+        let block = addr as *mut FreeBlock;
+        let mut prev: *mut FreeBlock = null_mut();
+        let mut next = self.head;
+
+        while !next.is_null() && (next as usize) < addr {
+            prev = next;
+            next = (*next).next;
+        }
+
+        (*block).size = size;
+        (*block).next = next;
+
+        if !next.is_null() && addr + size == next as usize {
+            (*block).size += (*next).size;
+            (*block).next = (*next).next;
+        }
+
+        if prev.is_null() {
+            self.head = block;
+        } else if prev as usize + (*prev).size == addr {
+            (*prev).size += (*block).size;
+            (*prev).next = (*block).next;
+        } else {
+            (*prev).next = block;
+        }
+    }
+
+    /// First-fit search, splitting the chosen block around the allocation.
+    unsafe fn take(&mut self, size: usize, align: usize) -> *mut u8 {
+        let mut prev: *mut FreeBlock = null_mut();
+        let mut current = self.head;
+
+        while !current.is_null() {
+            let block_start = current as usize;
+            let block_end = block_start + (*current).size;
+            let alloc_start = align_up(block_start, align);
+            let alloc_end = alloc_start + size;
+
+            if alloc_end <= block_end {
+                let next = (*current).next;
+
+                if prev.is_null() {
+                    self.head = next;
+                } else {
+                    (*prev).next = next;
+                }
+                if alloc_start > block_start {
+                    self.insert_free(block_start, alloc_start - block_start);
+                }
+                if block_end > alloc_end {
+                    self.insert_free(alloc_end, block_end - alloc_end);
+                }
+                self.used += size;
+                return alloc_start as *mut u8;
+            }
+
+            prev = current;
+            current = (*current).next;
+        }
+
+        null_mut()
+    }
+
+    fn block_layout(layout: Layout) -> (usize, usize) {
+        let size = align_up(layout.size().max(BLOCK_ALIGN), BLOCK_ALIGN);
+        let align = layout.align().max(BLOCK_ALIGN).max(align_of::<FreeBlock>());
+
+        (size, align)
+    }
+
+    unsafe fn alloc(&mut self, layout: Layout) -> *mut u8 {
+        let (size, align) = Self::block_layout(layout);
+        let ptr = self.take(size, align);
+
+        if !ptr.is_null() || !self.grow(size + align) {
+            return ptr;
+        }
+        self.take(size, align)
+    }
+
+    unsafe fn dealloc(&mut self, ptr: *mut u8, layout: Layout) {
+        let (size, _) = Self::block_layout(layout);
+
+        self.used -= size;
+        self.insert_free(ptr as usize, size);
+    }
+
+    pub fn stats(&self) -> HeapStats {
+        let size = self.end - self.start;
+
+        HeapStats {
+            size,
+            used: self.used,
+            free: size - self.used,
+        }
+    }
+}
+
+pub struct KernelHeap(Mutex<FreeListAllocator>);
+
+unsafe impl GlobalAlloc for KernelHeap {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        self.0.lock().alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        self.0.lock().dealloc(ptr, layout)
+    }
+}
+
+pub fn init() {
+    if !ALLOCATOR.0.lock().grow(HEAP_INITIAL_SIZE) {
+        panic!("Failed to map the initial kernel heap");
+    }
+}
+
+pub fn stats() -> HeapStats {
+    ALLOCATOR.0.lock().stats()
+}
@@ -0,0 +1,13 @@
+pub mod frame_allocator;
+pub mod heap;
+pub mod paging;
+
+use crate::multiboot::BootInfo;
+
+pub const PAGE_SIZE: usize = 0x1000;
+
+pub fn init(boot_info: &BootInfo) {
+    frame_allocator::FRAME_ALLOCATOR.lock().init(boot_info);
+    paging::init(boot_info);
+    heap::init();
+}
@@ -0,0 +1,314 @@
+//! 32-bit paging with 4 KiB pages and a recursively mapped page directory.
+//! https://wiki.osdev.org/Paging
+
+use crate::memory::frame_allocator::alloc_frame;
+use crate::memory::PAGE_SIZE;
+use crate::multiboot::BootInfo;
+use crate::utils::asm::{invlpg, read_cr0, write_cr0, write_cr3};
+use crate::{kernel_end, stack_guard};
+use core::ops::{BitAnd, BitOr};
+use core::ptr::addr_of_mut;
+use spin::Mutex;
+
+const ENTRY_COUNT: usize = 1024;
+
+/// The last directory entry points to the directory itself, so once paging is enabled the
+/// page tables are reachable at `RECURSIVE_TABLES_BASE` and the directory at its very end.
+const RECURSIVE_INDEX: usize = ENTRY_COUNT - 1;
+const RECURSIVE_TABLES_BASE: u32 = (RECURSIVE_INDEX as u32) << 22;
+const RECURSIVE_DIRECTORY: u32 = RECURSIVE_TABLES_BASE | (RECURSIVE_INDEX as u32) << 12;
+
+const CR0_WRITE_PROTECT: u32 = 1 << 16;
+const CR0_PAGING: u32 = 1 << 31;
+
+const ADDRESS_MASK: u32 = !(PAGE_SIZE as u32 - 1);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(transparent)]
+pub struct PageFlags(u32);
+
+#[allow(dead_code)]
+impl PageFlags {
+    pub const NONE: PageFlags = PageFlags(0);
+    pub const PRESENT: PageFlags = PageFlags(1 << 0);
+    pub const WRITABLE: PageFlags = PageFlags(1 << 1);
+    pub const USER: PageFlags = PageFlags(1 << 2);
+    pub const WRITE_THROUGH: PageFlags = PageFlags(1 << 3);
+    pub const CACHE_DISABLE: PageFlags = PageFlags(1 << 4);
+    pub const ACCESSED: PageFlags = PageFlags(1 << 5);
+    pub const DIRTY: PageFlags = PageFlags(1 << 6);
+    pub const GLOBAL: PageFlags = PageFlags(1 << 8);
+
+    pub const fn bits(self) -> u32 {
+        self.0
+    }
+
+    pub const fn from_bits(bits: u32) -> Self {
+        PageFlags(bits & !ADDRESS_MASK)
+    }
+
+    pub const fn contains(self, other: PageFlags) -> bool {
+        self.0 & other.0 == other.0
+    }
+}
+
+impl BitOr for PageFlags {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self {
+        PageFlags(self.0 | rhs.0)
+    }
+}
+
+impl BitAnd for PageFlags {
+    type Output = Self;
+
+    fn bitand(self, rhs: Self) -> Self {
+        PageFlags(self.0 & rhs.0)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MapError {
+    AlreadyMapped,
+    OutOfFrames,
+    /// The address belongs to the recursive mapping window.
+    ReservedAddress,
+}
+
+#[repr(C, align(4096))]
+struct PageTable {
+    entries: [u32; ENTRY_COUNT],
+}
+
+static mut KERNEL_PAGE_DIRECTORY: PageTable = PageTable {
+    entries: [0; ENTRY_COUNT],
+};
+
+pub static PAGING: Mutex<Paging> = Mutex::new(Paging { enabled: false });
+
+pub struct Paging {
+    enabled: bool,
+}
+
+fn directory_index(virt: u32) -> usize {
+    (virt >> 22) as usize
+}
+
+fn table_index(virt: u32) -> usize {
+    ((virt >> 12) & 0x3FF) as usize
+}
+
+impl Paging {
+    fn directory(&mut self) -> &mut PageTable {
+        if self.enabled {
+            unsafe { &mut *(RECURSIVE_DIRECTORY as *mut PageTable) }
+        } else {
+            unsafe { &mut *addr_of_mut!(KERNEL_PAGE_DIRECTORY) }
+        }
+    }
+
+    /// Page table covering the directory entry `index`, which must be present.
+    fn table(&mut self, index: usize) -> &mut PageTable {
+        let addr = if self.enabled {
+            RECURSIVE_TABLES_BASE + (index * PAGE_SIZE) as u32
+        } else {
+            self.directory().entries[index] & ADDRESS_MASK
+        };
+
+        unsafe { &mut *(addr as *mut PageTable) }
+    }
+
+    /// Returns the page table entry for `virt`, creating its page table if needed.
+    fn entry_or_create(&mut self, virt: u32, flags: PageFlags) -> Result<&mut u32, MapError> {
+        let dir_index = directory_index(virt);
+
+        if dir_index == RECURSIVE_INDEX {
+            return Err(MapError::ReservedAddress);
+        }
+
+        let table_flags = PageFlags::PRESENT | PageFlags::WRITABLE;
+        let dir_entry = self.directory().entries[dir_index];
+
+        if dir_entry & PageFlags::PRESENT.bits() == 0 {
+            let frame = alloc_frame().ok_or(MapError::OutOfFrames)?;
+
+            self.directory().entries[dir_index] = frame | table_flags.bits() | flags.bits();
+            if self.enabled {
+                invlpg(RECURSIVE_TABLES_BASE + (dir_index * PAGE_SIZE) as u32);
+            }
+            self.table(dir_index).entries = [0; ENTRY_COUNT];
+        } else if flags.contains(PageFlags::USER) {
+            self.directory().entries[dir_index] |= PageFlags::USER.bits();
+        }
+
+        Ok(&mut self.table(dir_index).entries[table_index(virt)])
+    }
+
+    /// Maps the 4 KiB page at `virt` to the frame at `phys`.
+    pub fn map(&mut self, virt: u32, phys: u32, flags: PageFlags) -> Result<(), MapError> {
+        let entry = self.entry_or_create(virt, flags & PageFlags::USER)?;
+
+        if *entry & PageFlags::PRESENT.bits() != 0 {
+            return Err(MapError::AlreadyMapped);
+        }
+
+        *entry = (phys & ADDRESS_MASK) | (flags | PageFlags::PRESENT).bits();
+        if self.enabled {
+            invlpg(virt);
+        }
+        Ok(())
+    }
+
+    /// Identity maps every page overlapping `[start; end[`, skipping the ones already mapped.
+    pub fn identity_map_range(
+        &mut self,
+        start: u32,
+        end: u32,
+        flags: PageFlags,
+    ) -> Result<(), MapError> {
+        let mut page = start & ADDRESS_MASK;
+
+        while page < end {
+            match self.map(page, page, flags) {
+                Ok(()) | Err(MapError::AlreadyMapped) => {}
+                Err(err) => return Err(err),
+            }
+            page = match page.checked_add(PAGE_SIZE as u32) {
+                Some(next) => next,
+                None => break,
+            };
+        }
+        Ok(())
+    }
+
+    /// Removes the mapping of the page at `virt` and returns the frame it pointed to.
+    pub fn unmap(&mut self, virt: u32) -> Option<u32> {
+        let dir_index = directory_index(virt);
+
+        if dir_index == RECURSIVE_INDEX
+            || self.directory().entries[dir_index] & PageFlags::PRESENT.bits() == 0
+        {
+            return None;
+        }
+
+        let entry = &mut self.table(dir_index).entries[table_index(virt)];
+        if *entry & PageFlags::PRESENT.bits() == 0 {
+            return None;
+        }
+
+        let phys = *entry & ADDRESS_MASK;
+        *entry = 0;
+        if self.enabled {
+            invlpg(virt);
+        }
+        Some(phys)
+    }
+
+    /// Returns the raw directory entry and, if the directory entry is present, the raw
+    /// table entry used to translate `virt`.
+    pub fn walk(&mut self, virt: u32) -> (u32, Option<u32>) {
+        let dir_index = directory_index(virt);
+        let dir_entry = self.directory().entries[dir_index];
+
+        if !self.enabled || dir_entry & PageFlags::PRESENT.bits() == 0 {
+            return (dir_entry, None);
+        }
+        (
+            dir_entry,
+            Some(self.table(dir_index).entries[table_index(virt)]),
+        )
+    }
+
+    /// Returns the physical address `virt` is mapped to, along with the page flags.
+    pub fn translate(&mut self, virt: u32) -> Option<(u32, PageFlags)> {
+        if !self.enabled {
+            return Some((virt, PageFlags::PRESENT | PageFlags::WRITABLE));
+        }
+
+        let dir_entry = self.directory().entries[directory_index(virt)];
+        if dir_entry & PageFlags::PRESENT.bits() == 0 {
+            return None;
+        }
+
+        let entry = self.table(directory_index(virt)).entries[table_index(virt)];
+        if entry & PageFlags::PRESENT.bits() == 0 {
+            return None;
+        }
+
+        Some((
+            (entry & ADDRESS_MASK) | (virt & !ADDRESS_MASK),
+            PageFlags::from_bits(entry),
+        ))
+    }
+
+    /// Identity maps the kernel, low memory and boot information, then turns paging on.
+    pub fn init(&mut self, boot_info: &BootInfo) -> Result<(), MapError> {
+        let kernel_flags = PageFlags::PRESENT | PageFlags::WRITABLE;
+        let directory_addr = addr_of_mut!(KERNEL_PAGE_DIRECTORY) as u32;
+
+        // Low memory holds the VGA buffer at 0xb8000. Page 0 stays unmapped so that null pointer
+        // dereferences fault.
+        self.identity_map_range(PAGE_SIZE as u32, kernel_end as u32, kernel_flags)?;
+        self.identity_map_range(boot_info.start, boot_info.end(), kernel_flags)?;
+        for module in boot_info.modules() {
+            self.identity_map_range(module.start, module.end, kernel_flags)?;
+        }
+        if let Some(sections) = boot_info.elf_sections {
+            for section in sections.iter().filter(|s| s.addr != 0) {
+                self.identity_map_range(section.addr, section.addr + section.size, kernel_flags)?;
+            }
+        }
+
+        self.unmap(stack_guard as u32);
+
+        self.directory().entries[RECURSIVE_INDEX] = directory_addr | kernel_flags.bits();
+
+        unsafe {
+            write_cr3(directory_addr);
+            write_cr0(read_cr0() | CR0_PAGING | CR0_WRITE_PROTECT);
+        }
+        self.enabled = true;
+        Ok(())
+    }
+}
+
+pub fn init(boot_info: &BootInfo) {
+    if let Err(err) = PAGING.lock().init(boot_info) {
+        panic!("Failed to set up paging: {:?}", err);
+    }
+}
+
+pub fn map(virt: u32, phys: u32, flags: PageFlags) -> Result<(), MapError> {
+    PAGING.lock().map(virt, phys, flags)
+}
+
+#[allow(dead_code)]
+pub fn unmap(virt: u32) -> Option<u32> {
+    PAGING.lock().unmap(virt)
+}
+
+#[allow(dead_code)]
+pub fn translate(virt: u32) -> Option<(u32, PageFlags)> {
+    PAGING.lock().translate(virt)
+}
+
+/// Identity maps `[start; end[` for firmware tables and memory-mapped devices, failing if one
+/// of these pages is already mapped to another frame.
+pub fn identity_map(start: u32, end: u32, flags: PageFlags) -> Result<(), MapError> {
+    let mut paging = PAGING.lock();
+    let mut page = start & ADDRESS_MASK;
+
+    while page < end {
+        match paging.translate(page) {
+            Some((phys, _)) if phys == page => {}
+            Some(_) => return Err(MapError::AlreadyMapped),
+            None => paging.map(page, page, flags)?,
+        }
+        page = match page.checked_add(PAGE_SIZE as u32) {
+            Some(next) => next,
+            None => break,
+        };
+    }
+    Ok(())
+}
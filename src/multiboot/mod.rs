@@ -0,0 +1,366 @@
+//! Parses the Multiboot2 boot information structure handed over by GRUB.
+//! https://www.gnu.org/software/grub/manual/multiboot2/multiboot.html#Boot-information-format
+
+use core::mem::size_of;
+use spin::Once;
+
+/// Value found in EAX when the kernel was loaded by a Multiboot2 compliant bootloader.
+pub const BOOTLOADER_MAGIC: u32 = 0x36d76289;
+
+pub const MAX_MODULES: usize = 8;
+
+const TAG_ALIGN: u32 = 8;
+
+const TAG_END: u32 = 0;
+const TAG_COMMAND_LINE: u32 = 1;
+const TAG_BOOTLOADER_NAME: u32 = 2;
+const TAG_MODULE: u32 = 3;
+const TAG_BASIC_MEMORY_INFO: u32 = 4;
+const TAG_MEMORY_MAP: u32 = 6;
+const TAG_FRAMEBUFFER: u32 = 8;
+const TAG_ELF_SECTIONS: u32 = 9;
+const TAG_ACPI_OLD_RSDP: u32 = 14;
+const TAG_ACPI_NEW_RSDP: u32 = 15;
+
+#[repr(C)]
+struct TagHeader {
+    typ: u32,
+    size: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MemoryRegionKind {
+    Available,
+    Reserved,
+    AcpiReclaimable,
+    AcpiNvs,
+    Defective,
+    Unknown(u32),
+}
+
+#[allow(dead_code)]
+#[derive(Debug, Clone, Copy)]
+#[repr(C, packed)]
+pub struct MemoryMapEntry {
+    pub base_addr: u64,
+    pub length: u64,
+    entry_type: u32,
+    reserved: u32,
+}
+
+impl MemoryMapEntry {
+    pub fn kind(&self) -> MemoryRegionKind {
+        match self.entry_type {
+            1 => MemoryRegionKind::Available,
+            2 => MemoryRegionKind::Reserved,
+            3 => MemoryRegionKind::AcpiReclaimable,
+            4 => MemoryRegionKind::AcpiNvs,
+            5 => MemoryRegionKind::Defective,
+            other => MemoryRegionKind::Unknown(other),
+        }
+    }
+}
+
+/// View over the entries of the memory map tag, entries are `entry_size` bytes apart.
+#[derive(Debug, Clone, Copy)]
+pub struct MemoryMap {
+    entries_addr: u32,
+    entry_size: u32,
+    count: u32,
+}
+
+impl MemoryMap {
+    pub fn iter(&self) -> impl Iterator<Item = MemoryMapEntry> + '_ {
+        (0..self.count).map(move |i| {
+            let addr = self.entries_addr + i * self.entry_size;
+            unsafe { core::ptr::read_unaligned(addr as *const MemoryMapEntry) }
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Module {
+    pub start: u32,
+    pub end: u32,
+    pub cmdline: &'static str,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FramebufferKind {
+    Indexed,
+    Rgb,
+    EgaText,
+    Unknown(u8),
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Framebuffer {
+    pub addr: u64,
+    pub pitch: u32,
+    pub width: u32,
+    pub height: u32,
+    pub bpp: u8,
+    pub kind: FramebufferKind,
+}
+
+#[allow(dead_code)]
+#[repr(C, packed)]
+struct FramebufferTag {
+    header: TagHeader,
+    addr: u64,
+    pitch: u32,
+    width: u32,
+    height: u32,
+    bpp: u8,
+    kind: u8,
+}
+
+/// 32-bit ELF section header, as described in the System V ABI.
+#[derive(Debug, Clone, Copy)]
+#[repr(C)]
+pub struct ElfSection {
+    pub name_index: u32,
+    pub section_type: u32,
+    pub flags: u32,
+    pub addr: u32,
+    pub offset: u32,
+    pub size: u32,
+    pub link: u32,
+    pub info: u32,
+    pub addr_align: u32,
+    pub entry_size: u32,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct ElfSections {
+    headers_addr: u32,
+    count: u32,
+    entry_size: u32,
+    string_table_index: u32,
+}
+
+impl ElfSections {
+    pub fn iter(&self) -> impl Iterator<Item = ElfSection> + '_ {
+        (0..self.count).map(move |i| self.get(i))
+    }
+
+    fn get(&self, index: u32) -> ElfSection {
+        let addr = self.headers_addr + index * self.entry_size;
+        unsafe { core::ptr::read_unaligned(addr as *const ElfSection) }
+    }
+
+    /// Looks up the section name in the section header string table, if GRUB loaded it.
+    pub fn name(&self, section: &ElfSection) -> &'static str {
+        if self.string_table_index >= self.count {
+            return "";
+        }
+
+        let string_table = self.get(self.string_table_index);
+        if string_table.addr == 0 {
+            return "";
+        }
+
+        c_str(string_table.addr + section.name_index, 64)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Rsdp {
+    pub revision: u8,
+    pub oem_id: [u8; 6],
+    pub rsdt_address: u32,
+    pub xsdt_address: Option<u64>,
+}
+
+#[allow(dead_code)]
+#[repr(C, packed)]
+struct RsdpDescriptor {
+    signature: [u8; 8],
+    checksum: u8,
+    oem_id: [u8; 6],
+    revision: u8,
+    rsdt_address: u32,
+    // Only present from revision 2
+    length: u32,
+    xsdt_address: u64,
+    extended_checksum: u8,
+    reserved: [u8; 3],
+}
+
+#[derive(Debug)]
+pub struct BootInfo {
+    /// Physical address and size of the whole boot information structure.
+    pub start: u32,
+    pub total_size: u32,
+    pub command_line: Option<&'static str>,
+    pub bootloader_name: Option<&'static str>,
+    /// Amount of lower and upper memory in kilobytes.
+    pub basic_memory: Option<(u32, u32)>,
+    pub memory_map: Option<MemoryMap>,
+    modules: [Option<Module>; MAX_MODULES],
+    pub framebuffer: Option<Framebuffer>,
+    pub elf_sections: Option<ElfSections>,
+    pub rsdp: Option<Rsdp>,
+}
+
+impl BootInfo {
+    pub fn modules(&self) -> impl Iterator<Item = &Module> {
+        self.modules.iter().flatten()
+    }
+
+    pub fn end(&self) -> u32 {
+        self.start + self.total_size
+    }
+
+    /// Value of a `key=value` option of the kernel command line.
+    pub fn command_line_option(&self, key: &str) -> Option<&'static str> {
+        self.command_line?
+            .split_whitespace()
+            .filter_map(|option| option.split_once('='))
+            .find(|&(name, _)| name == key)
+            .map(|(_, value)| value)
+    }
+
+    /// Walks every tag of the structure located at `addr`.
+    ///
+    /// # Safety
+    ///
+    /// `addr` must point to a valid Multiboot2 boot information structure.
+    unsafe fn parse(addr: u32) -> Self {
+        let total_size = *(addr as *const u32);
+        let mut info = BootInfo {
+            start: addr,
+            total_size,
+            command_line: None,
+            bootloader_name: None,
+            basic_memory: None,
+            memory_map: None,
+            modules: [None; MAX_MODULES],
+            framebuffer: None,
+            elf_sections: None,
+            rsdp: None,
+        };
+        let mut module_count = 0;
+
+        // The fixed part is `total_size` followed by a reserved u32
+        let mut tag_addr = addr + 8;
+
+        while tag_addr + size_of::<TagHeader>() as u32 <= info.end() {
+            let header = &*(tag_addr as *const TagHeader);
+            let body = tag_addr + size_of::<TagHeader>() as u32;
+            let body_len = header.size.saturating_sub(size_of::<TagHeader>() as u32) as usize;
+
+            match header.typ {
+                TAG_END => break,
+                TAG_COMMAND_LINE => {
+                    info.command_line = Some(c_str(body, body_len));
+                }
+                TAG_BOOTLOADER_NAME => {
+                    info.bootloader_name = Some(c_str(body, body_len));
+                }
+                TAG_MODULE => {
+                    let fields = body as *const u32;
+
+                    if module_count < MAX_MODULES {
+                        info.modules[module_count] = Some(Module {
+                            start: *fields,
+                            end: *fields.offset(1),
+                            cmdline: c_str(body + 8, body_len.saturating_sub(8)),
+                        });
+                        module_count += 1;
+                    }
+                }
+                TAG_BASIC_MEMORY_INFO => {
+                    let fields = body as *const u32;
+
+                    info.basic_memory = Some((*fields, *fields.offset(1)));
+                }
+                TAG_MEMORY_MAP => {
+                    let fields = body as *const u32;
+                    let entry_size = *fields;
+
+                    if entry_size != 0 {
+                        info.memory_map = Some(MemoryMap {
+                            entries_addr: body + 8,
+                            entry_size,
+                            count: (body_len as u32).saturating_sub(8) / entry_size,
+                        });
+                    }
+                }
+                TAG_FRAMEBUFFER => {
+                    let tag = core::ptr::read_unaligned(tag_addr as *const FramebufferTag);
+
+                    info.framebuffer = Some(Framebuffer {
+                        addr: tag.addr,
+                        pitch: tag.pitch,
+                        width: tag.width,
+                        height: tag.height,
+                        bpp: tag.bpp,
+                        kind: match tag.kind {
+                            0 => FramebufferKind::Indexed,
+                            1 => FramebufferKind::Rgb,
+                            2 => FramebufferKind::EgaText,
+                            other => FramebufferKind::Unknown(other),
+                        },
+                    });
+                }
+                TAG_ELF_SECTIONS => {
+                    // GRUB stores num, entsize and shndx as u32, unlike the specification
+                    let fields = body as *const u32;
+
+                    info.elf_sections = Some(ElfSections {
+                        count: *fields,
+                        entry_size: *fields.offset(1),
+                        string_table_index: *fields.offset(2),
+                        headers_addr: body + 12,
+                    });
+                }
+                TAG_ACPI_OLD_RSDP | TAG_ACPI_NEW_RSDP => {
+                    let descriptor = body as *const RsdpDescriptor;
+                    let revision = (*descriptor).revision;
+
+                    info.rsdp = Some(Rsdp {
+                        revision,
+                        oem_id: (*descriptor).oem_id,
+                        rsdt_address: (*descriptor).rsdt_address,
+                        xsdt_address: if header.typ == TAG_ACPI_NEW_RSDP && revision >= 2 {
+                            Some((*descriptor).xsdt_address)
+                        } else {
+                            None
+                        },
+                    });
+                }
+                _ => {}
+            }
+
+            if header.size < size_of::<TagHeader>() as u32 {
+                break;
+            }
+            tag_addr = (tag_addr + header.size + TAG_ALIGN - 1) & !(TAG_ALIGN - 1);
+        }
+
+        info
+    }
+}
+
+/// Reads a null-terminated string of at most `max_len` bytes.
+fn c_str(addr: u32, max_len: usize) -> &'static str {
+    let bytes = unsafe { core::slice::from_raw_parts(addr as *const u8, max_len) };
+
+    crate::u8_to_str!(bytes)
+}
+
+static BOOT_INFO: Once<BootInfo> = Once::new();
+
+/// Parses the boot information if `magic` shows we were booted by a Multiboot2 bootloader.
+pub fn init(magic: u32, info_addr: u32) -> Option<&'static BootInfo> {
+    if magic != BOOTLOADER_MAGIC || info_addr == 0 {
+        return None;
+    }
+
+    Some(BOOT_INFO.call_once(|| unsafe { BootInfo::parse(info_addr) }))
+}
+
+pub fn boot_info() -> Option<&'static BootInfo> {
+    BOOT_INFO.r#try()
+}
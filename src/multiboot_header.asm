@@ -19,6 +19,7 @@ section .text
 
 global _start
 
+global stack_guard
 global stack_bottom
 global stack_top
 
@@ -26,11 +27,17 @@ extern main
 
 _start:
     mov esp, stack_top
+    push ebx ; Multiboot2 boot information address
+    push eax ; Multiboot2 bootloader magic
     call main
     cli
     hlt
 
 section .bss
+align 4096
+; Left unmapped so that a stack overflow faults instead of corrupting memory
+stack_guard:
+    resb 0x1000
 stack_bottom:
     resb 0x5000
 stack_top:
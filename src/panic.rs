@@ -3,6 +3,8 @@ use core::arch::asm;
 use crate::halt;
 use crate::hexdump;
 use crate::println;
+use crate::serial::is_console_mirrored;
+use crate::serial_println;
 use crate::set_colors;
 use crate::stack_top;
 use crate::Color;
@@ -28,6 +30,12 @@ pub fn clean_registers() {
 fn panic(info: &PanicInfo) -> ! {
     set_colors(Some(Color::Red), None);
     println!("{}", info);
+    if !is_console_mirrored() {
+        serial_println!("{}", info);
+    }
+    if let Some(frame) = crate::interrupts::trap::current_trap_frame() {
+        println!("{}", frame);
+    }
     hexdump(unsafe { (stack_top as *const u8).offset(-0x80) }, 0x80);
     clean_registers();
     loop {
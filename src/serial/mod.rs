@@ -0,0 +1,204 @@
+//! 16550 UART serial ports. COM1 keeps a log of the kernel output that survives the screen
+//! scrolling, captured by QEMU with `-serial stdio`, and takes shell input with `console=serial`.
+//! https://wiki.osdev.org/Serial_Ports
+
+use crate::interrupts::without_interrupts;
+use crate::io::Port;
+use crate::utils::ring_buffer::RingBuffer;
+use core::fmt;
+use core::sync::atomic::{AtomicBool, Ordering};
+use spin::Mutex;
+
+pub const COM1: u16 = 0x3F8;
+pub const COM1_IRQ: u8 = 4;
+
+// Registers, offsets from the base port
+const DATA: u16 = 0;
+const INTERRUPT_ENABLE: u16 = 1;
+/// Divisor low and high bytes while DLAB is set.
+const DIVISOR_LOW: u16 = 0;
+const DIVISOR_HIGH: u16 = 1;
+const FIFO_CONTROL: u16 = 2;
+const LINE_CONTROL: u16 = 3;
+const MODEM_CONTROL: u16 = 4;
+const LINE_STATUS: u16 = 5;
+
+const INTERRUPT_ENABLE_DATA_AVAILABLE: u8 = 1 << 0;
+const LINE_CONTROL_8N1: u8 = 0b0000_0011;
+const LINE_CONTROL_DLAB: u8 = 1 << 7;
+/// Enable and clear both FIFOs, interrupt at 14 bytes.
+const FIFO_ENABLE_CLEAR_14: u8 = 0xC7;
+const MODEM_CONTROL_DTR: u8 = 1 << 0;
+const MODEM_CONTROL_RTS: u8 = 1 << 1;
+/// OUT2 gates the UART interrupt line on PCs.
+const MODEM_CONTROL_OUT2: u8 = 1 << 3;
+const MODEM_CONTROL_LOOPBACK: u8 = 1 << 4;
+const LINE_STATUS_DATA_READY: u8 = 1 << 0;
+const LINE_STATUS_TRANSMIT_EMPTY: u8 = 1 << 5;
+
+/// Frequency of the UART clock divided by 16, the fastest baud rate.
+const MAX_BAUD_RATE: u32 = 115_200;
+/// The output is sent while the kernel waits, a byte takes about 87 µs at this rate.
+pub const DEFAULT_BAUD_RATE: u32 = MAX_BAUD_RATE;
+/// Byte sent through the loopback to check that the UART works.
+const LOOPBACK_TEST_BYTE: u8 = 0xAE;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SerialError {
+    /// The byte sent in loopback mode didn't come back, there is no working UART.
+    LoopbackFailed,
+}
+
+pub struct SerialPort {
+    base: u16,
+    initialized: bool,
+}
+
+impl SerialPort {
+    pub const fn new(base: u16) -> SerialPort {
+        SerialPort {
+            base,
+            initialized: false,
+        }
+    }
+
+    fn register(&self, offset: u16) -> Port {
+        Port::new(self.base + offset)
+    }
+
+    /// Configures the port for 8 data bits, no parity and one stop bit at `baud_rate`.
+    pub fn init(&mut self, baud_rate: u32) -> Result<(), SerialError> {
+        let divisor = (MAX_BAUD_RATE / baud_rate.max(1)).clamp(1, 0xFFFF);
+
+        self.register(INTERRUPT_ENABLE).write(0);
+        self.register(LINE_CONTROL).write(LINE_CONTROL_DLAB);
+        self.register(DIVISOR_LOW).write(divisor as u8);
+        self.register(DIVISOR_HIGH).write((divisor >> 8) as u8);
+        self.register(LINE_CONTROL).write(LINE_CONTROL_8N1);
+        self.register(FIFO_CONTROL).write(FIFO_ENABLE_CLEAR_14);
+
+        self.register(MODEM_CONTROL)
+            .write(MODEM_CONTROL_RTS | MODEM_CONTROL_OUT2 | MODEM_CONTROL_LOOPBACK);
+        self.register(DATA).write(LOOPBACK_TEST_BYTE);
+        if self.register(DATA).read() != LOOPBACK_TEST_BYTE {
+            return Err(SerialError::LoopbackFailed);
+        }
+
+        self.register(MODEM_CONTROL)
+            .write(MODEM_CONTROL_DTR | MODEM_CONTROL_RTS | MODEM_CONTROL_OUT2);
+        self.initialized = true;
+        Ok(())
+    }
+
+    pub fn write_byte(&mut self, byte: u8) {
+        // Writing to a missing UART would wait forever
+        if !self.initialized {
+            return;
+        }
+
+        while self.register(LINE_STATUS).read() & LINE_STATUS_TRANSMIT_EMPTY == 0 {}
+        self.register(DATA).write(byte);
+    }
+
+    /// Raises the IRQ whenever a byte is received.
+    pub fn enable_receive_interrupt(&mut self) {
+        if self.initialized {
+            self.register(INTERRUPT_ENABLE)
+                .write(INTERRUPT_ENABLE_DATA_AVAILABLE);
+        }
+    }
+
+    /// Returns the next received byte, if any.
+    pub fn read_byte(&mut self) -> Option<u8> {
+        match self.initialized && self.register(LINE_STATUS).read() & LINE_STATUS_DATA_READY != 0 {
+            true => Some(self.register(DATA).read()),
+            false => None,
+        }
+    }
+}
+
+impl fmt::Write for SerialPort {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for byte in s.bytes() {
+            // Terminals expect CRLF line endings
+            if byte == b'\n' {
+                self.write_byte(b'\r');
+            }
+            self.write_byte(byte);
+        }
+        Ok(())
+    }
+}
+
+pub static SERIAL1: Mutex<SerialPort> = Mutex::new(SerialPort::new(COM1));
+
+/// Bytes received on COM1 by its interrupt, read by the shell.
+pub static RECEIVED: RingBuffer<u8, 256> = RingBuffer::new();
+
+/// Whether `print!` also writes to COM1.
+static MIRROR_CONSOLE: AtomicBool = AtomicBool::new(false);
+
+pub fn init(baud_rate: u32) -> Result<(), SerialError> {
+    without_interrupts(|| SERIAL1.lock().init(baud_rate))
+}
+
+pub fn enable_receive_interrupt() {
+    without_interrupts(|| SERIAL1.lock().enable_receive_interrupt());
+}
+
+/// Sends `bytes` on COM1, only holding off the interrupts while each byte is sent so that
+/// long outputs don't delay them.
+pub fn write_bytes(bytes: &[u8]) {
+    for &byte in bytes {
+        without_interrupts(|| SERIAL1.lock().write_byte(byte));
+    }
+}
+
+pub fn read_byte() -> Option<u8> {
+    without_interrupts(|| SERIAL1.lock().read_byte())
+}
+
+pub fn set_mirror_console(mirror: bool) {
+    MIRROR_CONSOLE.store(mirror, Ordering::SeqCst);
+}
+
+pub fn is_console_mirrored() -> bool {
+    MIRROR_CONSOLE.load(Ordering::SeqCst)
+}
+
+/// Formats to COM1 through `write_bytes`.
+struct Console;
+
+impl fmt::Write for Console {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for line in s.split_inclusive('\n') {
+            match line.strip_suffix('\n') {
+                // Terminals expect CRLF line endings
+                Some(line) => {
+                    write_bytes(line.as_bytes());
+                    write_bytes(b"\r\n");
+                }
+                None => write_bytes(line.as_bytes()),
+            }
+        }
+        Ok(())
+    }
+}
+
+#[doc(hidden)]
+pub fn _print(args: fmt::Arguments) {
+    use core::fmt::Write;
+
+    Console.write_fmt(args).unwrap();
+}
+
+#[macro_export]
+macro_rules! serial_print {
+    ($($arg:tt)*) => ($crate::serial::_print(format_args!($($arg)*)));
+}
+
+#[macro_export]
+macro_rules! serial_println {
+    () => ($crate::serial_print!("\n"));
+    ($($arg:tt)*) => ($crate::serial_print!("{}\n", format_args!($($arg)*)));
+}
@@ -0,0 +1,18 @@
+//! Time keeping: PIT ticks, TSC timestamps and the wall-clock date from the RTC.
+
+pub mod pit;
+pub mod rtc;
+pub mod timer;
+pub mod tsc;
+
+pub use core::time::Duration;
+
+/// Time elapsed since the PIT was programmed.
+pub fn uptime() -> Duration {
+    pit::uptime()
+}
+
+/// High-resolution timestamp from the TSC, or the PIT ticks when the TSC isn't usable.
+pub fn now() -> Duration {
+    tsc::now().unwrap_or_else(pit::uptime)
+}
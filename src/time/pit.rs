@@ -0,0 +1,122 @@
+//! Programmable Interval Timer: channel 0 raises IRQ 0 at a programmed frequency and its ticks
+//! are counted to measure time.
+//! https://wiki.osdev.org/Programmable_Interval_Timer
+
+use crate::interrupts::without_interrupts;
+use crate::io::Port;
+use core::ptr::{addr_of, addr_of_mut, read_volatile, write_volatile};
+use core::sync::atomic::{AtomicU32, Ordering};
+use core::time::Duration;
+
+/// Frequency of the oscillator feeding the PIT channels, in Hz.
+pub const BASE_FREQUENCY: u32 = 1_193_182;
+pub const DEFAULT_FREQUENCY: u32 = 1000;
+
+const CHANNEL_0_DATA: u16 = 0x40;
+const CHANNEL_2_DATA: u16 = 0x42;
+const COMMAND: u16 = 0x43;
+/// Channel 0, low then high byte of the reload value, mode 2 (rate generator), binary.
+const CHANNEL_0_RATE_GENERATOR: u8 = 0b0011_0100;
+/// Channel 2, low then high byte of the count, mode 0 (interrupt on terminal count), binary.
+const CHANNEL_2_ONE_SHOT: u8 = 0b1011_0000;
+
+/// Keyboard controller port B, which also gates channel 2 and reads its output.
+const PORT_B: u16 = 0x61;
+const PORT_B_CHANNEL_2_GATE: u8 = 1 << 0;
+const PORT_B_SPEAKER: u8 = 1 << 1;
+const PORT_B_CHANNEL_2_OUTPUT: u8 = 1 << 5;
+
+const NANOS_PER_SECOND: u64 = 1_000_000_000;
+
+/// Reload value of channel 0, the PIT ticks at `BASE_FREQUENCY / DIVISOR` Hz.
+/// 0 until `init`, the BIOS default being 65536.
+static DIVISOR: AtomicU32 = AtomicU32::new(0);
+
+/// Only written by the timer interrupt, 64-bit so it never wraps.
+static mut TICKS: u64 = 0;
+
+/// Programs channel 0 to tick at the closest reachable frequency to `frequency` Hz.
+pub fn init(frequency: u32) {
+    let divisor = (BASE_FREQUENCY + frequency / 2) / frequency.max(1);
+    let divisor = divisor.clamp(1, 0xFFFF);
+
+    without_interrupts(|| {
+        Port::new(COMMAND).write(CHANNEL_0_RATE_GENERATOR);
+        Port::new(CHANNEL_0_DATA).write(divisor as u8);
+        Port::new(CHANNEL_0_DATA).write((divisor >> 8) as u8);
+        DIVISOR.store(divisor, Ordering::SeqCst);
+    });
+}
+
+/// Spins until `cycles` periods of the base oscillator elapsed, counted by channel 2 with the
+/// speaker off. Doesn't need interrupts, so it works before they are enabled.
+pub fn wait_cycles(cycles: u16) {
+    let port_b = Port::new(PORT_B);
+    let saved = port_b.read();
+
+    // Channel 2 only counts while its gate is high, raise it once the count is written
+    port_b.write(saved & !(PORT_B_SPEAKER | PORT_B_CHANNEL_2_GATE));
+    Port::new(COMMAND).write(CHANNEL_2_ONE_SHOT);
+    Port::new(CHANNEL_2_DATA).write(cycles as u8);
+    Port::new(CHANNEL_2_DATA).write((cycles >> 8) as u8);
+    port_b.write((saved & !PORT_B_SPEAKER) | PORT_B_CHANNEL_2_GATE);
+
+    while port_b.read() & PORT_B_CHANNEL_2_OUTPUT == 0 {}
+
+    port_b.write(saved);
+}
+
+fn divisor() -> u64 {
+    match DIVISOR.load(Ordering::SeqCst) {
+        0 => 0x10000,
+        divisor => divisor as u64,
+    }
+}
+
+/// Actual tick frequency in Hz, rounded down.
+pub fn frequency() -> u32 {
+    (BASE_FREQUENCY as u64 / divisor()) as u32
+}
+
+/// Counts a tick, called from the timer interrupt. Returns the new tick count.
+pub fn tick() -> u64 {
+    unsafe {
+        let ticks = read_volatile(addr_of!(TICKS)) + 1;
+
+        write_volatile(addr_of_mut!(TICKS), ticks);
+        ticks
+    }
+}
+
+/// Ticks counted since boot.
+pub fn ticks() -> u64 {
+    // A 64-bit read takes two instructions, it must not be split by the timer interrupt
+    without_interrupts(|| unsafe { read_volatile(addr_of!(TICKS)) })
+}
+
+/// Converts a tick count to the time it represents at the programmed frequency.
+pub fn ticks_to_duration(ticks: u64) -> Duration {
+    let cycles = ticks * divisor();
+    let base = BASE_FREQUENCY as u64;
+
+    Duration::new(
+        cycles / base,
+        ((cycles % base) * NANOS_PER_SECOND / base) as u32,
+    )
+}
+
+/// Number of ticks spanning at least `duration`, saturating at `u64::MAX`.
+pub fn duration_to_ticks(duration: Duration) -> u64 {
+    let base = BASE_FREQUENCY as u64;
+    let subsec_cycles = duration.subsec_nanos() as u64 * base;
+    let cycles = duration
+        .as_secs()
+        .saturating_mul(base)
+        .saturating_add(subsec_cycles.div_ceil(NANOS_PER_SECOND));
+
+    cycles.div_ceil(divisor())
+}
+
+pub fn uptime() -> Duration {
+    ticks_to_duration(ticks())
+}
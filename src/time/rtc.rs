@@ -0,0 +1,267 @@
+//! CMOS Real-Time Clock, the wall-clock time kept by the battery-backed chip.
+//! https://wiki.osdev.org/CMOS
+
+use crate::acpi::find_table;
+use crate::interrupts::irq::{register_handler, IrqError};
+use crate::interrupts::{without_interrupts, TrapFrame};
+use crate::io::Port;
+use core::fmt;
+
+pub const RTC_IRQ: u8 = 8;
+
+const CMOS_INDEX: u16 = 0x70;
+const CMOS_DATA: u16 = 0x71;
+
+const REGISTER_SECONDS: u8 = 0x00;
+const REGISTER_MINUTES: u8 = 0x02;
+const REGISTER_HOURS: u8 = 0x04;
+const REGISTER_DAY: u8 = 0x07;
+const REGISTER_MONTH: u8 = 0x08;
+const REGISTER_YEAR: u8 = 0x09;
+const REGISTER_STATUS_A: u8 = 0x0A;
+const REGISTER_STATUS_B: u8 = 0x0B;
+const REGISTER_STATUS_C: u8 = 0x0C;
+
+const STATUS_A_UPDATE_IN_PROGRESS: u8 = 1 << 7;
+const STATUS_A_RATE_MASK: u8 = 0x0F;
+/// Stops the updates while the time is being set.
+const STATUS_B_SET: u8 = 1 << 7;
+const STATUS_B_PERIODIC_INTERRUPT: u8 = 1 << 6;
+const STATUS_B_24_HOUR: u8 = 1 << 1;
+const STATUS_B_BINARY: u8 = 1 << 2;
+const HOUR_PM: u8 = 1 << 7;
+
+/// Offset of the century register index in the FADT, 0 when there is none.
+const FADT_CENTURY_OFFSET: u32 = 108;
+const DEFAULT_CENTURY: u16 = 20;
+
+/// Periodic interrupt rates, the divider giving 32768 >> (rate - 1) Hz. Rates 1 and 2 don't work.
+const FASTEST_RATE: u8 = 3;
+const SLOWEST_RATE: u8 = 15;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RtcError {
+    /// Without a century register, only the years of `DEFAULT_CENTURY` can be stored.
+    YearOutOfRange(u16),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DateTime {
+    pub year: u16,
+    pub month: u8,
+    pub day: u8,
+    pub hour: u8,
+    pub minute: u8,
+    pub second: u8,
+}
+
+fn is_leap_year(year: u16) -> bool {
+    year % 4 == 0 && (year % 100 != 0 || year % 400 == 0)
+}
+
+fn days_in_month(year: u16, month: u8) -> u8 {
+    match month {
+        2 if is_leap_year(year) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+impl DateTime {
+    pub fn is_valid(&self) -> bool {
+        // The CMOS stores the century and the year as two digits each
+        self.year <= 9999
+            && (1..=12).contains(&self.month)
+            && (1..=days_in_month(self.year, self.month)).contains(&self.day)
+            && self.hour < 24
+            && self.minute < 60
+            && self.second < 60
+    }
+
+    /// Parses `YYYY-MM-DD` and `HH:MM:SS`.
+    pub fn parse(date: &str, time: &str) -> Option<DateTime> {
+        let mut date = date.split('-');
+        let mut time = time.split(':');
+        let date_time = DateTime {
+            year: date.next()?.parse().ok()?,
+            month: date.next()?.parse().ok()?,
+            day: date.next()?.parse().ok()?,
+            hour: time.next()?.parse().ok()?,
+            minute: time.next()?.parse().ok()?,
+            second: time.next()?.parse().ok()?,
+        };
+
+        match (date.next(), time.next(), date_time.is_valid()) {
+            (None, None, true) => Some(date_time),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for DateTime {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{:04}-{:02}-{:02} {:02}:{:02}:{:02}",
+            self.year, self.month, self.day, self.hour, self.minute, self.second
+        )
+    }
+}
+
+fn read_register(register: u8) -> u8 {
+    Port::new(CMOS_INDEX).write(register);
+    Port::new(CMOS_DATA).read()
+}
+
+fn write_register(register: u8, value: u8) {
+    Port::new(CMOS_INDEX).write(register);
+    Port::new(CMOS_DATA).write(value);
+}
+
+fn from_bcd(value: u8) -> u8 {
+    (value >> 4) * 10 + (value & 0x0F)
+}
+
+fn to_bcd(value: u8) -> u8 {
+    ((value / 10) << 4) | (value % 10)
+}
+
+/// CMOS register holding the century, advertised by the FADT.
+fn century_register() -> Option<u8> {
+    let fadt = find_table(b"FACP")?;
+    let (_, end) = fadt.body();
+    let offset = fadt.addr + FADT_CENTURY_OFFSET;
+
+    match offset < end {
+        true => Some(unsafe { *(offset as *const u8) }).filter(|&register| register != 0),
+        false => None,
+    }
+}
+
+/// Raw registers, in the order they're read and written.
+type Registers = [u8; 7];
+
+fn read_registers(century: Option<u8>) -> Registers {
+    while read_register(REGISTER_STATUS_A) & STATUS_A_UPDATE_IN_PROGRESS != 0 {}
+
+    [
+        read_register(REGISTER_SECONDS),
+        read_register(REGISTER_MINUTES),
+        read_register(REGISTER_HOURS),
+        read_register(REGISTER_DAY),
+        read_register(REGISTER_MONTH),
+        read_register(REGISTER_YEAR),
+        century.map_or(0, read_register),
+    ]
+}
+
+/// Reads the current date and time, converted to binary and 24-hour format.
+pub fn read() -> DateTime {
+    let century = century_register();
+
+    let (registers, status_b) = without_interrupts(|| {
+        // An update may start right after the flag was checked, read until two reads agree
+        let mut registers = read_registers(century);
+        loop {
+            let again = read_registers(century);
+            if again == registers {
+                break;
+            }
+            registers = again;
+        }
+        (registers, read_register(REGISTER_STATUS_B))
+    });
+
+    let [second, minute, hour, day, month, year, century_value] = registers;
+    let binary = status_b & STATUS_B_BINARY != 0;
+    let decode = |value: u8| if binary { value } else { from_bcd(value) };
+
+    let pm = hour & HOUR_PM != 0;
+    let mut hour = decode(hour & !HOUR_PM);
+    if status_b & STATUS_B_24_HOUR == 0 {
+        // 12 AM is midnight and 12 PM is noon
+        hour = (hour % 12) + if pm { 12 } else { 0 };
+    }
+
+    let century = match century {
+        Some(_) => decode(century_value) as u16,
+        None => DEFAULT_CENTURY,
+    };
+
+    DateTime {
+        year: century * 100 + decode(year) as u16,
+        month: decode(month),
+        day: decode(day),
+        hour,
+        minute: decode(minute),
+        second: decode(second),
+    }
+}
+
+/// Sets the date and time, keeping the encoding and hour format the CMOS is configured with.
+pub fn write(date_time: &DateTime) -> Result<(), RtcError> {
+    let century = century_register();
+
+    // The century would read back as `DEFAULT_CENTURY`
+    if century.is_none() && date_time.year / 100 != DEFAULT_CENTURY {
+        return Err(RtcError::YearOutOfRange(date_time.year));
+    }
+
+    without_interrupts(|| {
+        let status_b = read_register(REGISTER_STATUS_B);
+        let binary = status_b & STATUS_B_BINARY != 0;
+        let encode = |value: u8| if binary { value } else { to_bcd(value) };
+
+        let hour = match status_b & STATUS_B_24_HOUR {
+            0 => {
+                let pm = date_time.hour >= 12;
+                let hour = match date_time.hour % 12 {
+                    0 => 12,
+                    hour => hour,
+                };
+                encode(hour) | if pm { HOUR_PM } else { 0 }
+            }
+            _ => encode(date_time.hour),
+        };
+
+        write_register(REGISTER_STATUS_B, status_b | STATUS_B_SET);
+        write_register(REGISTER_SECONDS, encode(date_time.second));
+        write_register(REGISTER_MINUTES, encode(date_time.minute));
+        write_register(REGISTER_HOURS, hour);
+        write_register(REGISTER_DAY, encode(date_time.day));
+        write_register(REGISTER_MONTH, encode(date_time.month));
+        write_register(REGISTER_YEAR, encode((date_time.year % 100) as u8));
+        if let Some(register) = century {
+            write_register(register, encode((date_time.year / 100) as u8));
+        }
+        write_register(REGISTER_STATUS_B, status_b & !STATUS_B_SET);
+    });
+    Ok(())
+}
+
+fn rtc_isr(_: &mut TrapFrame) {
+    // The RTC raises no other interrupt until status C has been read
+    read_register(REGISTER_STATUS_C);
+}
+
+/// Enables the periodic interrupt on IRQ 8 at `frequency` Hz, a power of two from 2 to 8192.
+/// Returns the frequency actually programmed.
+pub fn enable_periodic_interrupt(frequency: u32) -> Result<u32, IrqError> {
+    // 32768 >> (rate - 1) Hz, rounded down to a power of two
+    let rate =
+        (frequency.clamp(1, 0x8000).leading_zeros() as u8 - 15).clamp(FASTEST_RATE, SLOWEST_RATE);
+
+    register_handler(RTC_IRQ, rtc_isr)?;
+
+    without_interrupts(|| {
+        let status_a = read_register(REGISTER_STATUS_A);
+        let status_b = read_register(REGISTER_STATUS_B);
+
+        write_register(REGISTER_STATUS_A, (status_a & !STATUS_A_RATE_MASK) | rate);
+        write_register(REGISTER_STATUS_B, status_b | STATUS_B_PERIODIC_INTERRUPT);
+        read_register(REGISTER_STATUS_C);
+    });
+
+    Ok(0x8000 >> (rate - 1))
+}
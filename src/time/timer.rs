@@ -0,0 +1,123 @@
+//! Timer queue: callbacks run from the timer interrupt once their deadline passed, and `sleep`
+//! to wait for a duration.
+
+use crate::interrupts::{are_interrupts_enabled, without_interrupts};
+use crate::time::{pit, Duration};
+use alloc::vec::Vec;
+use core::arch::asm;
+use core::sync::atomic::{AtomicU32, Ordering};
+use spin::Mutex;
+
+/// Runs in the timer interrupt, with interrupts disabled.
+pub type TimerCallback = fn();
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimerId(u32);
+
+struct Timer {
+    id: TimerId,
+    /// Tick count at which the callback runs.
+    deadline: u64,
+    /// Interval in ticks of a periodic timer.
+    period: Option<u64>,
+    callback: TimerCallback,
+}
+
+/// Ordered by deadline, the next timer to expire first.
+static TIMERS: Mutex<Vec<Timer>> = Mutex::new(Vec::new());
+static NEXT_ID: AtomicU32 = AtomicU32::new(0);
+
+fn insert(timer: Timer) {
+    without_interrupts(|| {
+        let mut timers = TIMERS.lock();
+        // After the timers with the same deadline, so they run in insertion order
+        let index = timers.partition_point(|other| other.deadline <= timer.deadline);
+
+        timers.insert(index, timer);
+    });
+}
+
+fn add(delay: Duration, period: Option<Duration>, callback: TimerCallback) -> TimerId {
+    let id = TimerId(NEXT_ID.fetch_add(1, Ordering::Relaxed));
+
+    insert(Timer {
+        id,
+        deadline: pit::ticks().saturating_add(pit::duration_to_ticks(delay)),
+        // A zero period would run the callback forever within a single tick
+        period: period.map(|period| pit::duration_to_ticks(period).max(1)),
+        callback,
+    });
+    id
+}
+
+/// Runs `callback` once, `delay` from now.
+#[allow(dead_code)]
+pub fn add_oneshot(delay: Duration, callback: TimerCallback) -> TimerId {
+    add(delay, None, callback)
+}
+
+/// Runs `callback` every `period`, starting `period` from now.
+pub fn add_periodic(period: Duration, callback: TimerCallback) -> TimerId {
+    add(period, Some(period), callback)
+}
+
+/// Removes a timer, returns `false` if it already expired or was cancelled.
+#[allow(dead_code)]
+pub fn cancel(id: TimerId) -> bool {
+    without_interrupts(|| {
+        let mut timers = TIMERS.lock();
+
+        match timers.iter().position(|timer| timer.id == id) {
+            Some(index) => {
+                timers.remove(index);
+                true
+            }
+            None => false,
+        }
+    })
+}
+
+/// Runs the callbacks whose deadline is `now` or earlier, called on every timer tick.
+pub fn run_expired(now: u64) {
+    loop {
+        // Release the lock before running the callback, so that it can add timers
+        let timer = {
+            let mut timers = TIMERS.lock();
+
+            match timers.first() {
+                Some(timer) if timer.deadline <= now => timers.remove(0),
+                _ => return,
+            }
+        };
+
+        (timer.callback)();
+
+        if let Some(period) = timer.period {
+            // Ticks missed while the interrupts were disabled are skipped rather than replayed
+            let deadline = timer.deadline.saturating_add(period).max(now + 1);
+
+            insert(Timer { deadline, ..timer });
+        }
+    }
+}
+
+/// Halts until `duration` elapsed.
+///
+/// Interrupts are enabled while waiting, so it must not be called from the timer interrupt.
+pub fn sleep(duration: Duration) {
+    let deadline = pit::ticks().saturating_add(pit::duration_to_ticks(duration));
+    let interrupts_enabled = are_interrupts_enabled();
+
+    while pit::ticks() < deadline {
+        // `sti` takes effect after `hlt` started, so the wake-up interrupt can't be missed
+        unsafe { asm!("sti", "hlt") };
+    }
+
+    if !interrupts_enabled {
+        unsafe { asm!("cli") };
+    }
+}
+
+pub fn sleep_ms(milliseconds: u64) {
+    sleep(Duration::from_millis(milliseconds));
+}
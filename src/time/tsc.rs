@@ -0,0 +1,82 @@
+//! Time Stamp Counter, counting CPU cycles. Its frequency is calibrated against the PIT to get
+//! timestamps far finer than the PIT ticks.
+//! https://wiki.osdev.org/TSC
+
+use crate::interrupts::without_interrupts;
+use crate::time::pit;
+use crate::utils::asm::{cpuid, rdtsc};
+use core::time::Duration;
+use spin::Once;
+
+const CPUID_FEATURES: u32 = 1;
+const CPUID_EDX_TSC: u32 = 1 << 4;
+
+/// Base oscillator periods measured by one calibration run, about 50 ms.
+const CALIBRATION_CYCLES: u16 = (pit::BASE_FREQUENCY / 20) as u16;
+const CALIBRATION_RUNS: usize = 3;
+
+const NANOS_PER_SECOND: u64 = 1_000_000_000;
+
+struct Tsc {
+    /// Cycles per second.
+    frequency: u64,
+    /// Counter value at calibration, the origin of the timestamps.
+    start: u64,
+}
+
+static TSC: Once<Tsc> = Once::new();
+
+pub fn is_supported() -> bool {
+    cpuid(CPUID_FEATURES).edx & CPUID_EDX_TSC != 0
+}
+
+/// Counts the TSC cycles elapsed during one calibration run.
+fn measure() -> u64 {
+    without_interrupts(|| {
+        let start = rdtsc();
+
+        pit::wait_cycles(CALIBRATION_CYCLES);
+        rdtsc() - start
+    })
+}
+
+/// Measures the TSC frequency, returns it in Hz or `None` without a TSC.
+pub fn init() -> Option<u64> {
+    if !is_supported() {
+        return None;
+    }
+
+    // A run can only be made longer by the machine (SMIs, a virtual CPU being descheduled)
+    let cycles = (0..CALIBRATION_RUNS).map(|_| measure()).min()?;
+    let frequency = cycles * pit::BASE_FREQUENCY as u64 / CALIBRATION_CYCLES as u64;
+
+    if frequency == 0 {
+        return None;
+    }
+
+    Some(
+        TSC.call_once(|| Tsc {
+            frequency,
+            start: rdtsc(),
+        })
+        .frequency,
+    )
+}
+
+/// Calibrated frequency in Hz.
+#[allow(dead_code)]
+pub fn frequency() -> Option<u64> {
+    TSC.r#try().map(|tsc| tsc.frequency)
+}
+
+/// Time elapsed since the calibration, with nanosecond resolution.
+pub fn now() -> Option<Duration> {
+    let tsc = TSC.r#try()?;
+    let cycles = rdtsc() - tsc.start;
+
+    // Split off the seconds so that the nanoseconds computation can't overflow
+    Some(Duration::new(
+        cycles / tsc.frequency,
+        ((cycles % tsc.frequency) * NANOS_PER_SECOND / tsc.frequency) as u32,
+    ))
+}
@@ -1,3 +1,5 @@
+use core::arch::asm;
+
 #[macro_export]
 macro_rules! halt {
     () => {
@@ -6,3 +8,97 @@ macro_rules! halt {
         }
     };
 }
+
+macro_rules! control_register {
+    ($read_name:ident, $write_name:ident, $register:literal) => {
+        #[allow(dead_code)]
+        pub fn $read_name() -> u32 {
+            let value: u32;
+
+            unsafe {
+                asm!(concat!("mov {}, ", $register), out(reg) value, options(nomem, nostack));
+            }
+            value
+        }
+
+        /// # Safety
+        ///
+        /// Writing a control register changes how the CPU behaves (paging, protection...).
+        #[allow(dead_code)]
+        pub unsafe fn $write_name(value: u32) {
+            asm!(concat!("mov ", $register, ", {}"), in(reg) value, options(nostack));
+        }
+    };
+}
+
+control_register!(read_cr0, write_cr0, "cr0");
+control_register!(read_cr2, write_cr2, "cr2");
+control_register!(read_cr3, write_cr3, "cr3");
+control_register!(read_cr4, write_cr4, "cr4");
+
+/// Flushes the TLB entry of the page containing `addr`.
+pub fn invlpg(addr: u32) {
+    unsafe {
+        asm!("invlpg [{}]", in(reg) addr, options(nostack));
+    }
+}
+
+#[allow(dead_code)]
+#[derive(Debug, Clone, Copy)]
+pub struct CpuidResult {
+    pub eax: u32,
+    pub ebx: u32,
+    pub ecx: u32,
+    pub edx: u32,
+}
+
+pub fn cpuid(leaf: u32) -> CpuidResult {
+    let (eax, ebx, ecx, edx): (u32, u32, u32, u32);
+
+    unsafe {
+        // EBX can't be an operand as LLVM reserves it, keep it in another register
+        asm!(
+            "mov {0:e}, ebx",
+            "cpuid",
+            "xchg {0:e}, ebx",
+            out(reg) ebx,
+            inout("eax") leaf => eax,
+            inout("ecx") 0 => ecx,
+            out("edx") edx,
+            options(nomem, nostack, preserves_flags)
+        );
+    }
+    CpuidResult { eax, ebx, ecx, edx }
+}
+
+/// Time Stamp Counter, incremented at a constant rate on CPUs with an invariant TSC.
+pub fn rdtsc() -> u64 {
+    let (low, high): (u32, u32);
+
+    unsafe {
+        asm!("rdtsc", out("eax") low, out("edx") high, options(nomem, nostack));
+    }
+    (high as u64) << 32 | low as u64
+}
+
+pub fn read_msr(msr: u32) -> u64 {
+    let (low, high): (u32, u32);
+
+    unsafe {
+        asm!("rdmsr", in("ecx") msr, out("eax") low, out("edx") high, options(nomem, nostack));
+    }
+    (high as u64) << 32 | low as u64
+}
+
+/// # Safety
+///
+/// Model specific registers control CPU features, writing a wrong value can crash the system.
+pub unsafe fn write_msr(msr: u32, value: u64) {
+    asm!(
+        "wrmsr",
+        in("ecx") msr,
+        in("eax") value as u32,
+        in("edx") (value >> 32) as u32,
+        options(nostack)
+    );
+}
@@ -1,4 +1,5 @@
-mod asm;
+pub mod asm;
+pub mod ring_buffer;
 
 #[macro_export]
 macro_rules! u8_to_str {
@@ -0,0 +1,60 @@
+//! Lock-free queue with a single producer and a single consumer, for an interrupt handler to
+//! hand data to the kernel without either waiting on the other.
+
+use core::cell::UnsafeCell;
+use core::mem::MaybeUninit;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+/// Holds up to `N - 1` values, one slot stays empty to tell a full buffer from an empty one.
+pub struct RingBuffer<T: Copy, const N: usize> {
+    slots: UnsafeCell<[MaybeUninit<T>; N]>,
+    /// Next slot to read, only written by the consumer.
+    head: AtomicUsize,
+    /// Next slot to write, only written by the producer.
+    tail: AtomicUsize,
+}
+
+// Each slot is accessed by one side at a time, handed over through `head` and `tail`
+unsafe impl<T: Copy + Send, const N: usize> Sync for RingBuffer<T, N> {}
+
+impl<T: Copy, const N: usize> RingBuffer<T, N> {
+    pub const fn new() -> Self {
+        RingBuffer {
+            slots: UnsafeCell::new([MaybeUninit::uninit(); N]),
+            head: AtomicUsize::new(0),
+            tail: AtomicUsize::new(0),
+        }
+    }
+
+    /// Appends `value`, handing it back if the buffer is full. Must only be called by the
+    /// producer.
+    pub fn push(&self, value: T) -> Result<(), T> {
+        let tail = self.tail.load(Ordering::Relaxed);
+        let next = (tail + 1) % N;
+
+        if next == self.head.load(Ordering::Acquire) {
+            return Err(value);
+        }
+
+        unsafe { (*self.slots.get())[tail] = MaybeUninit::new(value) };
+        self.tail.store(next, Ordering::Release);
+        Ok(())
+    }
+
+    /// Removes the oldest value. Must only be called by the consumer.
+    pub fn pop(&self) -> Option<T> {
+        let head = self.head.load(Ordering::Relaxed);
+
+        if head == self.tail.load(Ordering::Acquire) {
+            return None;
+        }
+
+        let value = unsafe { (*self.slots.get())[head].assume_init() };
+        self.head.store((head + 1) % N, Ordering::Release);
+        Some(value)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.head.load(Ordering::Acquire) == self.tail.load(Ordering::Acquire)
+    }
+}
@@ -0,0 +1,18 @@
+//! Code page 437, the character set of the VGA text mode font.
+//! https://en.wikipedia.org/wiki/Code_page_437
+
+/// Characters of the bytes 0x80 to 0xFF, in order.
+const UPPER_HALF: &str = "ÇüéâäàåçêëèïîìÄÅÉæÆôöòûùÿÖÜ¢£¥₧ƒáíóúñÑªº¿⌐¬½¼¡«»\
+                          ░▒▓│┤╡╢╖╕╣║╗╝╜╛┐└┴┬├─┼╞╟╚╔╩╦╠═╬╧╨╤╥╙╘╒╓╫╪┘┌█▄▌▐▀\
+                          αßΓπΣσµτΦΘΩδ∞φε∩≡±≥≤⌠⌡÷≈°∙·√ⁿ²■\u{A0}";
+
+/// Byte displaying `c`, if the font has it.
+pub fn from_char(c: char) -> Option<u8> {
+    match c {
+        ' '..='~' => Some(c as u8),
+        _ => UPPER_HALF
+            .chars()
+            .position(|upper| upper == c)
+            .map(|index| 0x80 + index as u8),
+    }
+}
@@ -1,3 +1,5 @@
+mod cp437;
+
 #[allow(dead_code)]
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 #[repr(u8)]
@@ -102,12 +104,11 @@ impl Writer {
     }
 
     pub fn write_string(&mut self, s: &str) {
-        for byte in s.bytes() {
-            match byte {
-                // printable ASCII byte or newline
-                0x20..=0x7e | b'\n' => self.write_byte(byte),
-                // not part of printable ASCII range
-                _ => self.write_byte(0xfe),
+        for c in s.chars() {
+            match c {
+                '\n' => self.new_line(),
+                // one cell per character, the font has no glyph for most of Unicode
+                _ => self.write_byte(cp437::from_char(c).unwrap_or(0xfe)),
             }
         }
     }
@@ -170,6 +171,10 @@ pub fn _print(args: fmt::Arguments) {
     without_interrupts(|| {
         WRITER.lock().write_fmt(args).unwrap();
     });
+    // Outside of the critical section, the serial port is much slower than the screen
+    if crate::serial::is_console_mirrored() {
+        crate::serial::_print(args);
+    }
 }
 
 #[macro_export]
@@ -184,7 +189,7 @@ macro_rules! println {
 }
 
 pub fn set_colors(foreground: Option<Color>, background: Option<Color>) {
-    WRITER.lock().set_colors(foreground, background)
+    crate::interrupts::without_interrupts(|| WRITER.lock().set_colors(foreground, background))
 }
 
 pub fn hexdump(view: *const u8, size: usize) {